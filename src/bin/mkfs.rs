@@ -0,0 +1,292 @@
+//! Formats a disk image with the native filesystem of Prestige and copies a directory tree into it.
+//!
+//! ```text
+//! mkfs <image> [<dir>]
+//! ```
+//!
+//! The beginning of the image is left untouched since it holds the kernel.
+
+use std::{
+    env,
+    error::Error,
+    fs::{self, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::Path,
+    process,
+};
+
+// the following values must match the layout used by the kernel in `src/kernel/fs`
+
+const KERNEL_SIZE: u64 = 4 << 20;
+
+const BLOCK_SIZE: usize = 512;
+const DATA_OFFSET: usize = 4;
+const DATA_SIZE: usize = BLOCK_SIZE - DATA_OFFSET;
+
+const SIGNATURE: &[u8; 8] = b"PRESTIGE";
+const VERSION: u8 = 3;
+const SUPERBLOCK_ADDR: u32 = 0;
+const BITMAP_ADDR: u32 = SUPERBLOCK_ADDR + 1;
+const BITS_PER_BLOCK: u32 = (BLOCK_SIZE * 8) as u32;
+
+const ENTRY_HEADER_SIZE: usize = 28;
+const MAX_NAME_LEN: usize = u8::MAX as usize;
+const KIND_DIR: u8 = 0;
+const KIND_FILE: u8 = 1;
+const KIND_LINK: u8 = 3;
+const KIND_FIFO: u8 = 4;
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_be_bytes(bytes)
+}
+
+fn set_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+fn set_u64(buf: &mut [u8], offset: usize, value: u64) {
+    buf[offset..offset + 8].copy_from_slice(&value.to_be_bytes());
+}
+
+/// Converts the `rwxrwxrwx` bits of a Unix mode into the permissions stored in an entry, as
+/// done by `ProcessPrivileges::from_mode`.
+fn permissions(mode: u32, kind: u8) -> u64 {
+    let shift = match kind {
+        KIND_DIR => 3,
+        KIND_LINK => 9,
+        KIND_FIFO => 12,
+        _ => 0,
+    };
+
+    let everyone = (mode & 0o7) as u64;
+    let group = (mode >> 3 & 0o7) as u64;
+    let owner = (mode >> 6 & 0o7) as u64;
+
+    let swap = |rwx: u64| (rwx & 0b100) >> 2 | (rwx & 0b010) | (rwx & 0b001) << 2;
+    swap(everyone) << shift | swap(group) << (15 + shift) | swap(owner) << (30 + shift)
+}
+
+/// The filesystem area of the image, built in memory before being written out.
+struct Disk {
+    data: Vec<u8>,
+    block_count: u32,
+    alloc_count: u32,
+}
+
+impl Disk {
+    fn new(block_count: u32) -> Self {
+        Self {
+            data: vec![0; block_count as usize * BLOCK_SIZE],
+            block_count,
+            alloc_count: 0,
+        }
+    }
+
+    fn block(&mut self, addr: u32) -> &mut [u8] {
+        let start = addr as usize * BLOCK_SIZE;
+        &mut self.data[start..start + BLOCK_SIZE]
+    }
+
+    fn bitmap_count(&self) -> u32 {
+        let n = self.block_count.saturating_sub(BITMAP_ADDR);
+        (n + BITS_PER_BLOCK) / (BITS_PER_BLOCK + 1)
+    }
+
+    fn data_addr(&self) -> u32 {
+        BITMAP_ADDR + self.bitmap_count()
+    }
+
+    fn alloc(&mut self) -> Result<u32, Box<dyn Error>> {
+        // blocks are handed out in order so the allocated ones are always the first ones
+        let i = self.alloc_count;
+        let addr = self.data_addr() + i;
+
+        if addr >= self.block_count {
+            return Err("the image is full".into());
+        }
+
+        let bitmap = self.block(BITMAP_ADDR + i / BITS_PER_BLOCK);
+        let bit = (i % BITS_PER_BLOCK) as usize;
+        bitmap[bit / 8] |= 1 << (bit % 8);
+
+        self.alloc_count += 1;
+        Ok(addr)
+    }
+
+    fn write_chain(&mut self, first: u32, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut addr = first;
+
+        for (i, chunk) in data.chunks(DATA_SIZE).enumerate() {
+            if i > 0 {
+                let next = self.alloc()?;
+                set_u32(self.block(addr), 0, next);
+                addr = next;
+            }
+
+            self.block(addr)[DATA_OFFSET..DATA_OFFSET + chunk.len()].copy_from_slice(chunk);
+        }
+
+        Ok(())
+    }
+
+    /// Appends an entry owned by root to the directory starting at block `dir`.
+    fn add_entry(
+        &mut self,
+        dir: u32,
+        kind: u8,
+        name: &str,
+        addr: u32,
+        size: u32,
+        mode: u32,
+    ) -> Result<u32, Box<dyn Error>> {
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(format!("invalid file name {name:?}").into());
+        }
+
+        let len = ENTRY_HEADER_SIZE + name.len();
+
+        let mut block = dir;
+
+        while get_u32(self.block(block), 0) != 0 {
+            block = get_u32(self.block(block), 0);
+        }
+
+        let mut pos = 0;
+
+        loop {
+            let data = &self.block(block)[DATA_OFFSET..];
+
+            if pos + ENTRY_HEADER_SIZE > data.len() || get_u32(data, pos + 1) == 0 {
+                break;
+            }
+
+            pos += ENTRY_HEADER_SIZE + data[pos + ENTRY_HEADER_SIZE - 1] as usize;
+        }
+
+        if pos + len > DATA_SIZE {
+            let next = self.alloc()?;
+            set_u32(self.block(block), 0, next);
+            block = next;
+            pos = 0;
+        }
+
+        let entry = &mut self.block(block)[DATA_OFFSET + pos..DATA_OFFSET + pos + len];
+        entry[0] = kind;
+        set_u32(entry, 1, addr);
+        set_u32(entry, 5, size);
+        set_u32(entry, 9, 0);
+        set_u32(entry, 13, 0);
+        set_u64(entry, 17, permissions(mode, kind));
+        entry[25..27].copy_from_slice(&1u16.to_be_bytes());
+        entry[ENTRY_HEADER_SIZE - 1] = name.len() as u8;
+        entry[ENTRY_HEADER_SIZE..].copy_from_slice(name.as_bytes());
+
+        Ok(len as u32)
+    }
+
+    /// Copies the content of `path` into the directory starting at block `dir` and returns
+    /// the size of its entries.
+    fn copy_dir(&mut self, path: &Path, dir: u32) -> Result<u32, Box<dyn Error>> {
+        let mut entries = fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let mut size = 0;
+
+        for entry in entries {
+            let name = entry
+                .file_name()
+                .into_string()
+                .map_err(|name| format!("{} is not valid unicode", Path::new(&name).display()))?;
+
+            let file_type = entry.file_type()?;
+            let mode = entry.metadata()?.permissions().mode();
+            let addr = self.alloc()?;
+
+            size += if file_type.is_dir() {
+                let dir_size = self.copy_dir(&entry.path(), addr)?;
+                self.add_entry(dir, KIND_DIR, &name, addr, dir_size, mode)?
+            } else if file_type.is_file() || file_type.is_symlink() {
+                // the content of a symbolic link is its target
+                let (kind, data) = if file_type.is_symlink() {
+                    let target = fs::read_link(entry.path())?;
+                    (KIND_LINK, target.to_string_lossy().as_bytes().to_vec())
+                } else {
+                    (KIND_FILE, fs::read(entry.path())?)
+                };
+
+                let file_size = u32::try_from(data.len())
+                    .map_err(|_| format!("{} is too large", entry.path().display()))?;
+
+                self.write_chain(addr, &data)?;
+                self.add_entry(dir, kind, &name, addr, file_size, mode)?
+            } else if file_type.is_fifo() {
+                self.add_entry(dir, KIND_FIFO, &name, addr, 0, mode)?
+            } else {
+                eprintln!("Skipping {}", entry.path().display());
+                continue;
+            };
+
+            println!("  {}", entry.path().display());
+        }
+
+        Ok(size)
+    }
+
+    fn write_superblock(&mut self) {
+        let (block_count, alloc_count) = (self.block_count, self.alloc_count);
+        let sb = self.block(SUPERBLOCK_ADDR);
+
+        sb[0..8].copy_from_slice(SIGNATURE);
+        sb[8] = VERSION;
+        set_u32(sb, 9, BLOCK_SIZE as u32);
+        set_u32(sb, 13, block_count);
+        set_u32(sb, 17, alloc_count);
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = env::args().collect::<Vec<_>>();
+
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("Usage: mkfs <image> [<dir>]");
+        process::exit(1);
+    }
+
+    let mut img = OpenOptions::new().read(true).write(true).open(&args[1])?;
+    let len = img.metadata()?.len();
+
+    if len <= KERNEL_SIZE {
+        return Err(format!("{} is too small to hold a filesystem", args[1]).into());
+    }
+
+    let block_count = u32::try_from((len - KERNEL_SIZE) / BLOCK_SIZE as u64)?;
+    let mut disk = Disk::new(block_count);
+
+    if disk.data_addr() >= block_count {
+        return Err(format!("{} is too small to hold a filesystem", args[1]).into());
+    }
+
+    // the root directory is always the first data block
+    disk.alloc()?;
+
+    if let Some(dir) = args.get(2) {
+        println!("Copying {dir} into {}", args[1]);
+        let root = disk.data_addr();
+        disk.copy_dir(Path::new(dir), root)?;
+    }
+
+    disk.write_superblock();
+
+    img.seek(SeekFrom::Start(KERNEL_SIZE))?;
+    img.write_all(&disk.data)?;
+
+    println!(
+        "Formatted {} ({} of {} blocks used)",
+        args[1], disk.alloc_count, block_count
+    );
+
+    Ok(())
+}
@@ -1,11 +1,13 @@
 pub mod arch;
-mod fs;
+pub(crate) mod fs;
 pub mod io;
+mod ipc;
 mod net;
 mod process;
 mod resource;
 pub mod scheduler;
 pub(crate) mod syscall;
+pub(crate) mod user;
 
 trait Initialize {
     fn init();
@@ -4,11 +4,11 @@ use crate::kernel::{
         console, exception,
         keyboard::{self, ALT, CTRL, SHIFT},
         println,
-        serial::SERIAL,
+        serial::{self, SERIAL},
     },
     syscall, Initialize,
 };
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{AtomicU64, Ordering};
 use lazy_static::lazy_static;
 use pc_keyboard::{DecodedKey, KeyCode, KeyState};
 use pic8259::ChainedPics;
@@ -38,6 +38,31 @@ fn irq_idx(n: u8) -> usize {
     (PIC1_OFFSET + n) as usize
 }
 
+// the PIT is left at its default rate, which gives about 18.2 ticks per second
+const PIT_FREQUENCY: u64 = 1_193_182;
+const PIT_DIVIDER: u64 = 65536;
+
+/// IRQs that have a handler, with the name of their device.
+pub(crate) const IRQS: [(u8, &str); 3] = [(0, "timer"), (1, "keyboard"), (4, "com1")];
+
+lazy_static! {
+    static ref IRQ_COUNTS: [AtomicU64; 16] = [(); 16].map(|_| AtomicU64::new(0));
+}
+
+/// Number of times IRQ `n` was raised since boot.
+pub(crate) fn irq_count(n: u8) -> u64 {
+    IRQ_COUNTS[n as usize].load(Ordering::Relaxed)
+}
+
+/// Milliseconds elapsed since the interrupts were enabled.
+pub(crate) fn uptime_ms() -> u64 {
+    irq_count(0) * PIT_DIVIDER * 1000 / PIT_FREQUENCY
+}
+
+fn count_irq(n: u8) {
+    IRQ_COUNTS[n as usize].fetch_add(1, Ordering::Relaxed);
+}
+
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
@@ -125,12 +150,16 @@ extern "x86-interrupt" fn segment_not_present_handler(sf: InterruptStackFrame, e
 }
 
 extern "x86-interrupt" fn timer_interrupt_handler(_sf: InterruptStackFrame) {
+    count_irq(0);
+
     unsafe {
         PICS.lock().notify_end_of_interrupt(irq_idx(0) as u8);
     }
 }
 
 extern "x86-interrupt" fn keyboard_interrupt_handler(_sf: InterruptStackFrame) {
+    count_irq(1);
+
     if let Some(ref mut kbd) = *keyboard::KEYBOARD.lock() {
         let scancode = keyboard::read_scancode();
 
@@ -157,6 +186,14 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_sf: InterruptStackFrame) {
                     DecodedKey::Unicode('\t') if is_shift => keyboard::send_csi('Z'),
                     DecodedKey::Unicode('\u{7f}') if is_ctrl && is_alt => syscall::reboot(),
                     DecodedKey::Unicode(c) => keyboard::send_key(c),
+                    DecodedKey::RawKey(KeyCode::PageUp) if is_shift => console::scroll(true),
+                    DecodedKey::RawKey(KeyCode::PageDown) if is_shift => console::scroll(false),
+                    DecodedKey::RawKey(KeyCode::F1) if is_alt => console::switch_to(0),
+                    DecodedKey::RawKey(KeyCode::F2) if is_alt => console::switch_to(1),
+                    DecodedKey::RawKey(KeyCode::F3) if is_alt => console::switch_to(2),
+                    DecodedKey::RawKey(KeyCode::F4) if is_alt => console::switch_to(3),
+                    DecodedKey::RawKey(KeyCode::F5) if is_alt => console::switch_to(4),
+                    DecodedKey::RawKey(KeyCode::F6) if is_alt => console::switch_to(5),
                     DecodedKey::RawKey(KeyCode::ArrowUp) => keyboard::send_csi('A'),
                     DecodedKey::RawKey(KeyCode::ArrowDown) => keyboard::send_csi('B'),
                     DecodedKey::RawKey(KeyCode::ArrowRight) => keyboard::send_csi('C'),
@@ -173,14 +210,10 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_sf: InterruptStackFrame) {
 }
 
 extern "x86-interrupt" fn com1_serial_interrupt_handler(_sf: InterruptStackFrame) {
-    let byte = SERIAL.lock().read_byte();
-    let key = match byte as char {
-        '\r' => '\n',
-        '\x7F' => '\x08',
-        c => c,
-    };
+    count_irq(4);
 
-    console::handle_key_inp(key);
+    let byte = SERIAL.lock().read_byte();
+    serial::receive(byte);
 
     unsafe {
         PICS.lock().notify_end_of_interrupt(irq_idx(4) as u8);
@@ -1,21 +1,27 @@
 pub(crate) mod allocator;
 
+use alloc::vec::Vec;
 use bootloader::{
     bootinfo::{MemoryMap, MemoryRegionType},
     BootInfo,
 };
 use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
 use x86_64::{
     instructions::interrupts as x86_64cint, // x86_64 crate interrupts
     registers::control::Cr3,
     structures::paging::{
-        FrameAllocator, FrameDeallocator, OffsetPageTable, PageTable, PhysFrame, Size4KiB,
+        FrameAllocator, FrameDeallocator, OffsetPageTable, PageTable, PageTableFlags, PhysFrame,
+        Size4KiB,
     },
     PhysAddr,
     VirtAddr,
 };
 
+pub(crate) const FRAME_SIZE: usize = 4096;
+
 static ALLOCATED_FRAMES: AtomicUsize = AtomicUsize::new(0);
+static FREE_FRAMES: Mutex<Vec<PhysFrame>> = Mutex::new(Vec::new()); // given back, used first
 static mut PHYS_MEM_OFFSET: u64 = 0;
 static mut MEMORY_MAP: Option<&MemoryMap> = None;
 
@@ -33,6 +39,108 @@ pub(crate) fn init(boot_info: &'static BootInfo) {
     });
 }
 
+/// Number of frames of usable memory reported by the bootloader.
+pub(crate) fn total_frames() -> usize {
+    match unsafe { MEMORY_MAP } {
+        Some(mem_map) => BootInfoFrameAllocator { mem_map }.usable_frames().count(),
+        None => 0,
+    }
+}
+
+/// Number of frames in use.
+pub(crate) fn allocated_frames() -> usize {
+    ALLOCATED_FRAMES.load(Ordering::SeqCst) - FREE_FRAMES.lock().len()
+}
+
+/// Allocates `count` frames filled with zeros, returning their physical addresses.
+pub(crate) fn alloc_frames(count: usize) -> Option<Vec<u64>> {
+    let mut frame_alloc = unsafe { BootInfoFrameAllocator::init(MEMORY_MAP?) };
+    let mut frames = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let Some(frame) = frame_alloc.allocate_frame() else {
+            free_frames(&frames);
+            return None;
+        };
+
+        let addr = frame.start_address().as_u64();
+        unsafe { core::ptr::write_bytes((PHYS_MEM_OFFSET + addr) as *mut u8, 0, FRAME_SIZE) };
+        frames.push(addr);
+    }
+
+    Some(frames)
+}
+
+/// Gives frames allocated by `alloc_frames` back, they must not be mapped anymore.
+pub(crate) fn free_frames(frames: &[u64]) {
+    let Some(mem_map) = (unsafe { MEMORY_MAP }) else {
+        return;
+    };
+
+    let mut frame_alloc = BootInfoFrameAllocator { mem_map };
+
+    for &addr in frames {
+        unsafe { frame_alloc.deallocate_frame(PhysFrame::containing_address(PhysAddr::new(addr))) };
+    }
+}
+
+/// Makes a level 4 page table sharing every entry of the active one, except the entry covering
+/// `private` which starts empty, returning its physical address.
+pub(crate) fn new_page_table(private: u64) -> Option<u64> {
+    let table = alloc_frames(1)?[0];
+
+    unsafe {
+        let active = active_level_4_table(VirtAddr::new(PHYS_MEM_OFFSET));
+        let new = page_table(table);
+        let private = usize::from(VirtAddr::new(private).p4_index());
+
+        for (i, entry) in active.iter().enumerate().filter(|&(i, _)| i != private) {
+            new[i] = entry.clone();
+        }
+    }
+
+    Some(table)
+}
+
+/// Gives back a table made by `new_page_table` along with the tables below its private entry,
+/// the pages mapped there must have been unmapped.
+pub(crate) fn free_page_table(table: u64, private: u64) {
+    let mut frames = Vec::from([table]);
+    let private = usize::from(VirtAddr::new(private).p4_index());
+
+    // the level 3 table, then the level 2 and 1 tables under it
+    let l4: &PageTable = unsafe { page_table(table) };
+    let mut tables = Vec::from([(&l4[private], 3)]);
+
+    while let Some((entry, level)) = tables.pop() {
+        if entry.is_unused() || entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+            continue;
+        }
+
+        let addr = entry.addr().as_u64();
+        frames.push(addr);
+
+        if level > 1 {
+            let table: &PageTable = unsafe { page_table(addr) };
+            tables.extend(table.iter().map(|entry| (entry, level - 1)));
+        }
+    }
+
+    free_frames(&frames);
+}
+
+/// The page table whose level 4 table is at the physical address `table`, or the active one.
+unsafe fn table_mapper(table: Option<u64>) -> OffsetPageTable<'static> {
+    match table {
+        Some(table) => OffsetPageTable::new(page_table(table), VirtAddr::new(PHYS_MEM_OFFSET)),
+        None => mapper(VirtAddr::new(PHYS_MEM_OFFSET)),
+    }
+}
+
+unsafe fn page_table(addr: u64) -> &'static mut PageTable {
+    &mut *((PHYS_MEM_OFFSET + addr) as *mut PageTable)
+}
+
 unsafe fn mapper(phys_mem_offset: VirtAddr) -> OffsetPageTable<'static> {
     let level_4_table = active_level_4_table(phys_mem_offset);
     OffsetPageTable::new(level_4_table, phys_mem_offset)
@@ -69,7 +177,17 @@ impl BootInfoFrameAllocator {
 
 unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
     fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        if let Some(frame) = FREE_FRAMES.lock().pop() {
+            return Some(frame);
+        }
+
         let next = ALLOCATED_FRAMES.fetch_add(1, Ordering::SeqCst);
         self.usable_frames().nth(next)
     }
 }
+
+impl FrameDeallocator<Size4KiB> for BootInfoFrameAllocator {
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
+        FREE_FRAMES.lock().push(frame);
+    }
+}
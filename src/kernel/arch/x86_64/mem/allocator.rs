@@ -1,18 +1,19 @@
 use crate::k::{io::recoverable, process};
+use alloc::vec::Vec;
 use linked_list_allocator::LockedHeap;
 use x86_64::{
     structures::paging::{
-        mapper::MapToError, page::PageRangeInclusive, FrameAllocator, Mapper, Page, PageTableFlags,
-        Size4KiB,
+        mapper::MapToError, page::PageRangeInclusive, FrameAllocator, FrameDeallocator, Mapper,
+        Page, PageTableFlags, PhysFrame, Size4KiB,
     },
-    VirtAddr,
+    PhysAddr, VirtAddr,
 };
 
 #[global_allocator]
 static ALLOCATOR: LockedHeap = LockedHeap::empty();
 
 pub(crate) const HEAP_START: usize = 0x_4444_4444_0000;
-pub(crate) const HEAP_SIZE: usize = 100 * 1024;
+pub(crate) const HEAP_SIZE: usize = 4 << 20;
 
 pub(super) fn init_heap(
     mapper: &mut impl Mapper<Size4KiB>,
@@ -44,6 +45,11 @@ pub(super) fn init_heap(
     Ok(())
 }
 
+/// Bytes of the kernel heap currently in use.
+pub(crate) fn heap_used() -> usize {
+    ALLOCATOR.lock().used()
+}
+
 pub(crate) fn alloc(addr: u64, size: usize) -> Result<(), ()> {
     let mut mapper = unsafe { super::mapper(VirtAddr::new(super::PHYS_MEM_OFFSET)) };
     let mut frame_alloc =
@@ -76,8 +82,43 @@ pub(crate) fn alloc(addr: u64, size: usize) -> Result<(), ()> {
     Ok(())
 }
 
-pub(crate) fn free(addr: u64, size: usize) {
-    let mut mapper = unsafe { super::mapper(VirtAddr::new(super::PHYS_MEM_OFFSET)) };
+/// Maps the frames at the physical addresses `frames` one after the other from `addr`, where
+/// user space can reach them, in the page table at `table` or the active one.
+pub(crate) fn map(table: Option<u64>, addr: u64, frames: &[u64]) -> Result<(), ()> {
+    let mut mapper = unsafe { super::table_mapper(table) };
+    let mut frame_alloc =
+        unsafe { super::BootInfoFrameAllocator::init(super::MEMORY_MAP.unwrap()) };
+    let flags =
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+
+    let start_page = Page::<Size4KiB>::containing_address(VirtAddr::new(addr));
+
+    for (i, &frame) in frames.iter().enumerate() {
+        let page = start_page + i as u64;
+        let frame = PhysFrame::containing_address(PhysAddr::new(frame));
+
+        unsafe {
+            let Ok(mapping) = mapper.map_to(page, frame, flags, &mut frame_alloc) else {
+                recoverable!("Unable to map {:?}", page);
+                return Err(());
+            };
+
+            // only the active table is cached
+            match table {
+                Some(_) => mapping.ignore(),
+                None => mapping.flush(),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the pages of the `size` bytes at `addr` from the page table at `table` or the active
+/// one, returning the frames that were behind them.
+pub(crate) fn unmap(table: Option<u64>, addr: u64, size: usize) -> Vec<PhysFrame> {
+    let mut mapper = unsafe { super::table_mapper(table) };
+    let mut frames = Vec::new();
 
     let pages: PageRangeInclusive<Size4KiB> = {
         let start_page = Page::containing_address(VirtAddr::new(addr));
@@ -86,12 +127,29 @@ pub(crate) fn free(addr: u64, size: usize) {
     };
 
     for page in pages {
-        if let Ok((_, mapping)) = mapper.unmap(page) {
-            mapping.flush();
+        if let Ok((frame, mapping)) = mapper.unmap(page) {
+            match table {
+                Some(_) => mapping.ignore(),
+                None => mapping.flush(),
+            }
+
+            frames.push(frame);
         } else {
             recoverable!("Unable to unmap {:?}", page);
         }
     }
+
+    frames
+}
+
+/// Unmaps memory given by `alloc`, its frames are given back.
+pub(crate) fn free(addr: u64, size: usize) {
+    let mut frame_alloc =
+        unsafe { super::BootInfoFrameAllocator::init(super::MEMORY_MAP.unwrap()) };
+
+    for frame in unmap(None, addr, size) {
+        unsafe { frame_alloc.deallocate_frame(frame) };
+    }
 }
 
 #[alloc_error_handler]
@@ -1,6 +1,376 @@
-use super::resource::Resource;
-use alloc::{boxed::Box, string::String};
+pub(crate) mod block;
+pub(crate) mod cache;
+pub(crate) mod devfs;
+pub(crate) mod disk;
+pub(crate) mod ext2;
+pub(crate) mod fat;
+pub(crate) mod native;
+pub(crate) mod partition;
+pub(crate) mod procfs;
+
+use self::{
+    block::{BlockDevice, MemDisk, Partition, BLOCK_SIZE},
+    devfs::DevFs,
+    ext2::Ext2Fs,
+    fat::FatFs,
+    native::NativeFs,
+    procfs::ProcFs,
+};
+use super::{
+    io::{kprint, recoverable},
+    ipc::pipe,
+    process::{self, ProcessPrivileges},
+    resource::{Device, Resource},
+};
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
 use bitflags::bitflags;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+
+// the boot disk starts with the kernel image, the native filesystem lives right after it
+// (`src/bin/mkfs.rs` relies on the same layout)
+const KERNEL_SIZE: u32 = 4 << 20;
+
+// size of the disk kept in memory when no drive is attached
+const MEMDISK_SIZE: u32 = 512 << 10;
+
+// where the filesystems found on the other drives get mounted
+const MOUNT_DIR: &str = "/mnt";
+
+const DEV_DIR: &str = "/dev";
+
+const PROC_DIR: &str = "/proc";
+
+// how many symbolic links are followed before giving up on a path
+const MAX_SYMLINKS: usize = 8;
+
+/// Operations provided by a filesystem driver.
+///
+/// Nodes are identified by an address whose meaning is up to the driver (a block, a cluster,
+/// an inode...). Reads and writes happen at the offset of the file, moving it is left to the
+/// caller. Read-only drivers keep the default implementation of the other methods.
+pub(crate) trait FileSystem: Send {
+    fn root(&self) -> u32;
+    fn entries(&mut self, dir: &Directory) -> Result<Vec<DirEntry>, ()>;
+    fn read(&mut self, file: &File, buf: &mut [u8]) -> Result<usize, ()>;
+
+    fn write(&mut self, file: &mut File, buf: &[u8]) -> Result<usize, ()> {
+        Err(())
+    }
+
+    fn create(&mut self, dir: &mut Directory, name: &str, kind: FileKind) -> Result<DirEntry, ()> {
+        Err(())
+    }
+
+    fn truncate(&mut self, file: &mut File) -> Result<(), ()> {
+        Err(())
+    }
+
+    /// Deletes `entry` along with its content, directories are only removed once empty.
+    fn remove(&mut self, entry: &DirEntry) -> Result<(), ()> {
+        Err(())
+    }
+
+    /// Moves `entry` into `dir` under `name`, both on this filesystem and with no such entry yet.
+    fn rename(&mut self, entry: &DirEntry, dir: &mut Directory, name: &str) -> Result<(), ()> {
+        Err(())
+    }
+
+    /// Gives the node of `entry`, which is not a directory, another name in `dir`, with the same
+    /// requirements as `rename`.
+    fn link(&mut self, entry: &DirEntry, dir: &mut Directory, name: &str) -> Result<(), ()> {
+        Err(())
+    }
+
+    /// Completes `meta`, which already holds what the VFS knows about `entry`.
+    ///
+    /// The root of the filesystem is given as an entry with an empty name.
+    fn metadata(&mut self, entry: &DirEntry, meta: &mut Metadata) -> Result<(), ()> {
+        Ok(())
+    }
+
+    /// Stores the owner and the permissions of `entry`, filesystems without them ignore it.
+    fn set_owner(
+        &mut self,
+        entry: &DirEntry,
+        user: u64,
+        group: u64,
+        permissions: ProcessPrivileges,
+    ) -> Result<(), ()> {
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<(), ()> {
+        Ok(())
+    }
+}
+
+struct Mount {
+    id: usize,
+    path: String,
+    fs: Box<dyn FileSystem>,
+    disk: Option<String>, // the disk the filesystem lives on
+}
+
+static MOUNTS: Mutex<Vec<Mount>> = Mutex::new(Vec::new());
+
+pub(crate) fn init() {
+    disk::init();
+
+    // partitioned drives are only looked at through their partitions
+    let mut found = Vec::new();
+
+    for disk in disk::disks().into_iter().filter(|d| !d.has_partitions()) {
+        let name = String::from(disk.name());
+
+        if let Some((kind, fs)) = probe(Box::new(disk)) {
+            found.push((name, kind, fs));
+        }
+    }
+
+    // the root is the filesystem of the boot drive, or its first native partition
+    let root = match boot_fs() {
+        Some((name, fs)) => Some((Some(name), Box::new(fs) as Box<dyn FileSystem>)),
+        None => match found.iter().position(|(_, kind, _)| *kind == "native") {
+            Some(i) => {
+                let (name, _, fs) = found.remove(i);
+                Some((Some(name), fs))
+            }
+            None => {
+                kprint!("No disk found, using an in-memory filesystem\n");
+                NativeFs::format(Box::new(MemDisk::new(MEMDISK_SIZE / BLOCK_SIZE as u32)))
+                    .map(|fs| (None, Box::new(fs) as Box<dyn FileSystem>))
+            }
+        },
+    };
+
+    let Some((disk, root)) = root else {
+        recoverable!("Could not mount the root filesystem");
+        return;
+    };
+
+    match disk {
+        Some(name) => mount_disk("/", root, &name),
+        None => mount("/", root),
+    }
+
+    devfs::init();
+    mount(DEV_DIR, Box::new(DevFs));
+    mount(PROC_DIR, Box::new(ProcFs));
+
+    for (name, kind, fs) in found {
+        let path = format!("{}/{}", MOUNT_DIR, name);
+
+        if Directory::open(MOUNT_DIR).is_none() {
+            Directory::create(MOUNT_DIR);
+        }
+
+        kprint!("Mounted {} filesystem on {}\n", kind, path);
+        mount_disk(&path, fs, &name);
+    }
+}
+
+// the native filesystem following the kernel on an unpartitioned boot drive, formatted if needed,
+// along with the name of the drive
+fn boot_fs() -> Option<(String, NativeFs)> {
+    let offset = KERNEL_SIZE / BLOCK_SIZE as u32;
+
+    // the first drive is the one we booted from
+    let disk = disk::disks().into_iter().next()?;
+
+    if disk.has_partitions() || disk.block_count() <= offset {
+        return None;
+    }
+
+    let count = disk.block_count() - offset;
+    let name = String::from(disk.name());
+
+    let fs = match NativeFs::open(Box::new(Partition::new(Box::new(disk), offset, count))) {
+        Ok(fs) => Some(fs),
+        Err(dev) => {
+            kprint!("No filesystem found, formatting the disk\n");
+            NativeFs::format(dev)
+        }
+    };
+
+    fs.map(|fs| (name, fs))
+}
+
+/// Finds out which filesystem `dev` holds and opens it with the matching driver.
+pub(crate) fn probe(dev: Box<dyn BlockDevice>) -> Option<(&'static str, Box<dyn FileSystem>)> {
+    let dev = match NativeFs::open(dev) {
+        Ok(fs) => return Some(("native", Box::new(fs))),
+        Err(dev) => dev,
+    };
+
+    let dev = match FatFs::open(dev) {
+        Ok(fs) => return Some(("FAT32", Box::new(fs))),
+        Err(dev) => dev,
+    };
+
+    match Ext2Fs::open(dev) {
+        Ok(fs) => Some(("ext2", Box::new(fs))),
+        Err(_) => None,
+    }
+}
+
+/// Makes the content of `fs` reachable under `path`, replacing what was mounted there.
+pub(crate) fn mount(path: &str, fs: Box<dyn FileSystem>) {
+    attach(path, fs, None);
+}
+
+/// Mounts `fs` like `mount`, the disk named `disk` it lives on can't be written to directly
+/// until it is unmounted.
+pub(crate) fn mount_disk(path: &str, fs: Box<dyn FileSystem>, disk: &str) {
+    attach(path, fs, Some(disk.into()));
+}
+
+fn attach(path: &str, fs: Box<dyn FileSystem>, disk: Option<String>) {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+    let path = realpath(path);
+    unmount(&path).ok();
+
+    if let Some(name) = &disk {
+        disk::set_mounted(name, true);
+    }
+
+    MOUNTS.lock().push(Mount {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        path,
+        fs,
+        disk,
+    });
+}
+
+/// Writes back and detaches the filesystem mounted at `path`.
+pub(crate) fn unmount(path: &str) -> Result<(), ()> {
+    let mut mounts = MOUNTS.lock();
+    let i = mounts.iter().position(|m| m.path == path).ok_or(())?;
+
+    mounts[i].fs.sync()?;
+
+    if let Some(name) = mounts.remove(i).disk {
+        disk::set_mounted(&name, false);
+    }
+
+    Ok(())
+}
+
+/// Writes every pending change of the mounted filesystems back to their device.
+pub(crate) fn sync() -> Result<(), ()> {
+    let mut res = Ok(());
+
+    for mount in MOUNTS.lock().iter_mut() {
+        if mount.fs.sync().is_err() {
+            res = Err(());
+        }
+    }
+
+    res
+}
+
+fn with_fs<T>(mount: usize, f: impl FnOnce(&mut dyn FileSystem) -> Result<T, ()>) -> Result<T, ()> {
+    let mut mounts = MOUNTS.lock();
+    let mount = mounts.iter_mut().find(|m| m.id == mount).ok_or(())?;
+
+    f(mount.fs.as_mut())
+}
+
+// the mount closest to `path` along with the path of its mount point
+fn resolve(path: &str) -> Option<(usize, String)> {
+    MOUNTS
+        .lock()
+        .iter()
+        .filter(|m| m.path == "/" || path == m.path || path.starts_with(&format!("{}/", m.path)))
+        .max_by_key(|m| m.path.len())
+        .map(|m| (m.id, m.path.clone()))
+}
+
+/// Makes `path` absolute and removes its `.` and `..` components, relative paths start from
+/// the directory of the current process.
+pub(crate) fn realpath(path: &str) -> String {
+    let path = if path.starts_with('/') {
+        path.into()
+    } else {
+        format!("{}/{}", process::current_process().dir(), path)
+    };
+
+    let mut names = Vec::new();
+
+    for name in path.split('/') {
+        match name {
+            "" | "." => {}
+            ".." => {
+                names.pop();
+            }
+            _ => names.push(name),
+        }
+    }
+
+    format!("/{}", names.join("/"))
+}
+
+pub(crate) fn dirname(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) => "/",
+        Some(i) => &path[..i],
+        None => "",
+    }
+}
+
+pub(crate) fn filename(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(i) => &path[i + 1..],
+        None => path,
+    }
+}
+
+/// Same as `realpath` but symbolic links along the path are replaced by their target, the last
+/// component is only followed when `follow` is set.
+///
+/// Missing nodes are left as they are, `None` is returned when a loop of links is found.
+pub(crate) fn canonicalize(path: &str, follow: bool) -> Option<String> {
+    // the components left to look at, in reverse order
+    let components = |path: &str| -> Vec<String> {
+        path.split('/')
+            .rev()
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect()
+    };
+
+    let mut rest = components(&realpath(path));
+    let mut res = String::from("/");
+    let mut links = 0;
+
+    while let Some(name) = rest.pop() {
+        let entry = Directory::open_resolved(&res).and_then(|dir| dir.find(&name));
+
+        match entry {
+            Some(entry) if entry.is_link() && (follow || !rest.is_empty()) => {
+                links += 1;
+
+                if links > MAX_SYMLINKS {
+                    return None;
+                }
+
+                // absolute targets start from the root, relative ones from the directory
+                // holding the link
+                let target = entry.link_target()?;
+                let target = match target.starts_with('/') {
+                    true => realpath(&target),
+                    false => realpath(&format!("{}/{}", res, target)),
+                };
+                rest.extend(components(&target));
+                res = "/".into();
+            }
+            _ => res = format!("{}/{}", res.trim_end_matches('/'), name),
+        }
+    }
+
+    Some(res)
+}
 
 pub(crate) trait FileIO {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()>;
@@ -17,62 +387,314 @@ pub(crate) struct File {
 }
 
 impl File {
-    pub(crate) fn create(path: &str) -> Self {
-        todo!();
+    pub(crate) fn create(path: &str) -> Option<Self> {
+        let path = canonicalize(path, false)?;
+        let mut dir = Directory::open(dirname(&path))?;
+
+        dir.create_entry(FileKind::File, filename(&path))
+            .map(DirEntry::into_file)
     }
 
     pub(crate) fn open(path: &str) -> Option<Self> {
-        todo!();
+        let path = canonicalize(path, true)?;
+        let dir = Directory::open(dirname(&path))?;
+        let entry = dir.find(filename(&path))?;
+
+        entry.is_file().then(|| entry.into_file())
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) const fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub(crate) fn metadata(&self) -> Option<Metadata> {
+        DirEntry {
+            dir: self.contained_by.clone(),
+            name: self.name.clone(),
+            addr: self.addr,
+            size: self.size,
+            kind: FileKind::File,
+        }
+        .metadata()
+    }
+
+    // takes the size and the address from the entry of the file, which other descriptions of
+    // it may have changed
+    fn refresh(&mut self) -> Result<(), ()> {
+        let entry = self.contained_by.find(&self.name).ok_or(())?;
+        self.size = entry.size;
+        self.addr = entry.addr;
+        Ok(())
+    }
+
+    /// Moves the offset of the file, see `seek_offset`. Writing past the end fills the gap with
+    /// zeros.
+    pub(crate) fn seek(&mut self, offset: isize, flag: SeekFlag) -> Result<usize, ()> {
+        // the end is wherever the last writer of the file left it
+        if flag == SeekFlag::END {
+            self.refresh()?;
+        }
+
+        let offset = seek_offset(self.offset as u64, self.size as u64, offset, flag)?;
+        self.offset = u32::try_from(offset).map_err(|_| ())?;
+        Ok(offset as usize)
+    }
+
+    // writes the zeros of the gap left by seeking past the end
+    fn fill_gap(&mut self) -> Result<(), ()> {
+        let end = self.offset;
+        let zeros = [0; BLOCK_SIZE];
+        self.offset = self.size;
+
+        while self.offset < end {
+            let len = (end - self.offset).min(BLOCK_SIZE as u32) as usize;
+            let n = with_fs(self.contained_by.mount, |fs| fs.write(self, &zeros[..len]))?;
+
+            if n == 0 {
+                return Err(());
+            }
+
+            self.offset += n as u32;
+        }
+
+        Ok(())
+    }
+
+    /// Drops the content of the file.
+    pub(crate) fn truncate(&mut self) -> Result<(), ()> {
+        with_fs(self.contained_by.mount, |fs| fs.truncate(self))?;
+        self.offset = 0;
+        Ok(())
     }
 }
 
 impl FileIO for File {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
-        todo!();
+        let n = with_fs(self.contained_by.mount, |fs| fs.read(self, buf))?;
+        self.offset += n as u32;
+        Ok(n)
     }
 
     fn write(&mut self, buf: &[u8]) -> Result<usize, ()> {
-        todo!();
+        if self.offset > self.size {
+            self.refresh()?;
+        }
+
+        if self.offset > self.size {
+            self.fill_gap()?;
+        }
+
+        let n = with_fs(self.contained_by.mount, |fs| fs.write(self, buf))?;
+        self.offset += n as u32;
+        Ok(n)
     }
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct Directory {
+    mount: usize,
     parent: Option<Box<Directory>>,
     name: String,
     addr: u32,
     size: u32,
+    offset: u32, // index of the next entry read through `FileIO`
 }
 
 impl Directory {
+    pub(crate) fn root() -> Option<Self> {
+        let (mount, _) = resolve("/")?;
+        Self::mount_root(mount, "")
+    }
+
+    fn mount_root(mount: usize, name: &str) -> Option<Self> {
+        let addr = with_fs(mount, |fs| Ok(fs.root())).ok()?;
+
+        Some(Self {
+            mount,
+            parent: None,
+            name: name.into(),
+            addr,
+            size: 0,
+            offset: 0,
+        })
+    }
+
+    pub(crate) fn create(path: &str) -> Option<Self> {
+        let path = canonicalize(path, false)?;
+        let mut dir = Self::open(dirname(&path))?;
+
+        dir.create_entry(FileKind::Dir, filename(&path))
+            .map(DirEntry::into_dir)
+    }
+
     pub(crate) fn open(path: &str) -> Option<Self> {
-        todo!();
+        Self::open_resolved(&canonicalize(path, true)?)
+    }
+
+    // opens an absolute path without symbolic links, as given by `canonicalize`
+    fn open_resolved(path: &str) -> Option<Self> {
+        let (mount, mount_path) = resolve(path)?;
+        let mut dir = Self::mount_root(mount, filename(&mount_path))?;
+
+        for name in path[mount_path.len()..].split('/') {
+            if name.is_empty() {
+                continue;
+            }
+
+            let entry = dir.find(name)?;
+
+            if !entry.is_dir() {
+                return None;
+            }
+
+            dir = entry.into_dir();
+        }
+
+        Some(dir)
     }
 
     pub(crate) fn is_root(&self) -> bool {
         self.parent.is_none()
     }
 
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Absolute path of the directory.
+    pub(crate) fn path(&self) -> String {
+        match &self.parent {
+            Some(parent) => format!("{}/{}", parent.path().trim_end_matches('/'), self.name),
+            None => MOUNTS
+                .lock()
+                .iter()
+                .find(|m| m.id == self.mount)
+                .map(|m| m.path.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn metadata(&self) -> Option<Metadata> {
+        let entry = DirEntry {
+            dir: match &self.parent {
+                Some(parent) => *parent.clone(),
+                None => self.clone(),
+            },
+            name: if self.is_root() {
+                String::new()
+            } else {
+                self.name.clone()
+            },
+            addr: self.addr,
+            size: self.size,
+            kind: FileKind::Dir,
+        };
+
+        entry.metadata()
+    }
+
     pub(crate) fn entries(&self) -> DirEntries {
-        DirEntries { dir: self.clone() }
+        let mut entries = with_fs(self.mount, |fs| fs.entries(self)).unwrap_or_default();
+        let path = self.path();
+
+        // filesystems mounted right below this directory show up as subdirectories
+        for mount in MOUNTS.lock().iter() {
+            let name = filename(&mount.path);
+
+            if mount.path != "/"
+                && dirname(&mount.path) == path
+                && !entries.iter().any(|entry| entry.name == name)
+            {
+                entries.push(DirEntry {
+                    dir: self.clone(),
+                    name: name.into(),
+                    addr: mount.fs.root(),
+                    size: 0,
+                    kind: FileKind::Dir,
+                });
+            }
+        }
+
+        DirEntries {
+            entries: entries.into_iter(),
+        }
+    }
+
+    pub(crate) fn find(&self, name: &str) -> Option<DirEntry> {
+        self.entries().find(|entry| entry.name == name)
+    }
+
+    // new nodes belong to the current process and get its privileges as permissions
+    fn create_entry(&mut self, kind: FileKind, name: &str) -> Option<DirEntry> {
+        if name.is_empty() || name.contains('/') || self.find(name).is_some() {
+            return None;
+        }
+
+        let proc = process::current_process();
+
+        with_fs(self.mount, |fs| {
+            let entry = fs.create(self, name, kind)?;
+            fs.set_owner(
+                &entry,
+                proc.user().inner(),
+                proc.group().inner(),
+                proc.privileges(),
+            )?;
+            Ok(entry)
+        })
+        .ok()
     }
 }
 
 pub(crate) struct DirEntries {
-    dir: Directory,
+    entries: vec::IntoIter<DirEntry>,
 }
 
 impl Iterator for DirEntries {
     type Item = DirEntry;
 
     fn next(&mut self) -> Option<Self::Item> {
-        todo!();
+        self.entries.next()
     }
 }
 
+/// Reading a directory gives as many of its entries as `buf` can hold, each of them stored as
+/// its kind (1 byte) and the length of its name (1 byte) followed by the name.
 impl FileIO for Directory {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
-        todo!();
+        let mut n = 0;
+
+        for entry in self.entries().skip(self.offset as usize) {
+            let name = entry.name.as_bytes();
+            let len = 2 + name.len();
+
+            if name.len() > u8::MAX as usize {
+                self.offset += 1;
+                continue;
+            }
+
+            if n + len > buf.len() {
+                // the entry will come with the next read
+                if n == 0 {
+                    return Err(());
+                }
+
+                break;
+            }
+
+            buf[n] = entry.kind as u8;
+            buf[n + 1] = name.len() as u8;
+            buf[n + 2..n + len].copy_from_slice(name);
+
+            n += len;
+            self.offset += 1;
+        }
+
+        Ok(n)
     }
 
     fn write(&mut self, buf: &[u8]) -> Result<usize, ()> {
@@ -84,6 +706,24 @@ impl FileIO for Directory {
 pub(crate) enum FileKind {
     Dir = 0,
     File = 1,
+    Device = 2,
+    Link = 3,
+    Fifo = 4,
+}
+
+impl TryFrom<u8> for FileKind {
+    type Error = ();
+
+    fn try_from(kind: u8) -> Result<Self, Self::Error> {
+        match kind {
+            0 => Ok(FileKind::Dir),
+            1 => Ok(FileKind::File),
+            2 => Ok(FileKind::Device),
+            3 => Ok(FileKind::Link),
+            4 => Ok(FileKind::Fifo),
+            _ => Err(()),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -91,9 +731,246 @@ pub(crate) struct DirEntry {
     dir: Directory,
     name: String,
     addr: u32,
+    size: u32,
     kind: FileKind,
 }
 
+impl DirEntry {
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) const fn kind(&self) -> FileKind {
+        self.kind
+    }
+
+    pub(crate) const fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub(crate) fn is_dir(&self) -> bool {
+        self.kind == FileKind::Dir
+    }
+
+    pub(crate) fn is_file(&self) -> bool {
+        self.kind == FileKind::File
+    }
+
+    pub(crate) fn is_link(&self) -> bool {
+        self.kind == FileKind::Link
+    }
+
+    pub(crate) fn is_fifo(&self) -> bool {
+        self.kind == FileKind::Fifo
+    }
+
+    // the buffer of a FIFO belongs to the node, whatever its name
+    fn fifo_id(&self) -> pipe::FifoId {
+        (self.dir.mount, self.addr)
+    }
+
+    /// The path a symbolic link points to, which is stored as its content.
+    pub(crate) fn link_target(&self) -> Option<String> {
+        if !self.is_link() {
+            return None;
+        }
+
+        let mut buf = vec![0; self.size as usize];
+        let n = self.clone().into_file().read(&mut buf).ok()?;
+
+        String::from_utf8(buf[..n].to_vec()).ok()
+    }
+
+    pub(crate) fn metadata(&self) -> Option<Metadata> {
+        let mut meta = Metadata::new(self.kind, self.size as u64);
+        meta.device = self.dir.mount as u64;
+
+        with_fs(self.dir.mount, |fs| fs.metadata(self, &mut meta)).ok()?;
+        Some(meta)
+    }
+
+    pub(crate) fn into_dir(self) -> Directory {
+        Directory {
+            mount: self.dir.mount,
+            parent: Some(Box::new(self.dir)),
+            name: self.name,
+            addr: self.addr,
+            size: self.size,
+            offset: 0,
+        }
+    }
+
+    pub(crate) fn into_file(self) -> File {
+        File {
+            contained_by: self.dir,
+            name: self.name,
+            addr: self.addr,
+            size: self.size,
+            offset: 0,
+        }
+    }
+}
+
+/// Information about a node, as returned to user space by the INFO syscalls.
+///
+/// Owners are root and everything is allowed on filesystems that don't store them, timestamps
+/// are in seconds since the Unix epoch and 0 when unknown.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Metadata {
+    kind: u64,
+    size: u64,
+    user: u64,
+    group: u64,
+    permissions: u64,
+    device: u64, // the mount holding the node
+    accessed: u64,
+    modified: u64,
+    created: u64,
+    links: u64, // the number of names of the node
+}
+
+impl Metadata {
+    pub(crate) fn new(kind: FileKind, size: u64) -> Self {
+        Self {
+            kind: kind as u64,
+            size,
+            permissions: ProcessPrivileges::from_mode(0o777, kind).bits(),
+            links: 1,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn kind(&self) -> Option<FileKind> {
+        FileKind::try_from(self.kind as u8).ok()
+    }
+
+    pub(crate) const fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub(crate) const fn user(&self) -> u64 {
+        self.user
+    }
+
+    pub(crate) const fn group(&self) -> u64 {
+        self.group
+    }
+
+    pub(crate) const fn permissions(&self) -> ProcessPrivileges {
+        ProcessPrivileges::from_bits_truncate(self.permissions)
+    }
+
+    pub(crate) const fn device(&self) -> u64 {
+        self.device
+    }
+
+    pub(crate) const fn links(&self) -> u64 {
+        self.links
+    }
+
+    /// Whether the current process may access the node, root is allowed everything.
+    ///
+    /// The privileges of the owner apply to the owner of the node, then those of the group
+    /// apply to its members and those of everyone to the others.
+    pub(crate) fn allows(&self, access: Access) -> bool {
+        let proc = process::current_process();
+
+        if proc.user().is_root() {
+            return true;
+        }
+
+        let shift = if self.user == proc.user().inner() {
+            6
+        } else if self.group == proc.group().inner() {
+            3
+        } else {
+            0
+        };
+
+        let Some(kind) = self.kind() else {
+            return false;
+        };
+
+        self.permissions()
+            .contains(ProcessPrivileges::from_mode((access as u32) << shift, kind))
+    }
+
+    fn check(&self, access: Access) -> Result<(), FsError> {
+        match self.allows(access) {
+            true => Ok(()),
+            false => Err(FsError::PermissionDenied),
+        }
+    }
+}
+
+/// Why an operation on the filesystem or on an open resource failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FsError {
+    PermissionDenied,
+    BrokenPipe, // written to a pipe nobody can read anymore
+    Failed,     // the node does not exist, the device failed...
+}
+
+impl From<()> for FsError {
+    fn from(_: ()) -> Self {
+        FsError::Failed
+    }
+}
+
+/// What a process wants to do with a node, valued as the matching bit of a Unix mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Access {
+    Read = 4,
+    Write = 2,
+    Exec = 1,
+}
+
+// the directories leading to `path`, which must be absolute, have to be searchable
+fn check_path(path: &str) -> Result<(), FsError> {
+    let mut dir = String::new();
+
+    for name in dirname(path).split('/') {
+        dir = format!("{}/{}", dir.trim_end_matches('/'), name);
+
+        // missing directories are reported by the operation itself
+        if let Some(meta) = Directory::open(&dir).and_then(|dir| dir.metadata()) {
+            meta.check(Access::Exec)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the current process may access the node at `path`, which must be absolute, and
+/// search the directories leading to it.
+pub(crate) fn check_access(path: &str, access: Access) -> Result<(), FsError> {
+    check_path(path)?;
+    metadata(path).ok_or(FsError::Failed)?.check(access)
+}
+
+/// The metadata of the node at `path`, symbolic links are followed.
+pub(crate) fn metadata(path: &str) -> Option<Metadata> {
+    let path = canonicalize(path, true)?;
+
+    // mount points are only seen for what they are when opened as directories
+    if let Some(dir) = Directory::open(&path) {
+        return dir.metadata();
+    }
+
+    Directory::open(dirname(&path))?
+        .find(filename(&path))?
+        .metadata()
+}
+
+/// The metadata of the node at `path` as seen by the current process, which has to be able to
+/// search the directories leading to it.
+pub(crate) fn info(path: &str) -> Result<Metadata, FsError> {
+    let path = canonicalize(path, true).ok_or(FsError::Failed)?;
+    check_path(&path)?;
+    metadata(&path).ok_or(FsError::Failed)
+}
+
 bitflags! {
     pub(crate) struct OpenFlag: u8 {
         const READ = 1;
@@ -104,6 +981,7 @@ bitflags! {
         const TRUNCATE = 1 << 4;
         const DIR = 1 << 5;
         const DEVICE = 1 << 6;
+        const CLOSE_ON_EXEC = 1 << 7; // the handle is closed when the process runs another program
     }
 
     pub(crate) struct SeekFlag: u8 {
@@ -113,22 +991,296 @@ bitflags! {
     }
 }
 
-pub(crate) fn open(path: &str, flags: usize) -> Option<Resource> {
-    let open_flag = OpenFlag::from_bits(flags as u8)?;
+/// Creates an empty directory at `path`.
+pub(crate) fn mkdir(path: &str) -> Result<(), FsError> {
+    let path = canonicalize(path, false).ok_or(FsError::Failed)?;
+    check_access(dirname(&path), Access::Write)?;
+
+    Directory::create(&path).map(|_| ()).ok_or(FsError::Failed)
+}
+
+/// Deletes the empty directory at `path`, mount points can't be removed.
+pub(crate) fn rmdir(path: &str) -> Result<(), FsError> {
+    let path = canonicalize(path, false).ok_or(FsError::Failed)?;
+    check_access(dirname(&path), Access::Write)?;
+
+    let dir = Directory::open_resolved(&path).ok_or(FsError::Failed)?;
+
+    if dir.is_root() || dir.entries().next().is_some() {
+        return Err(FsError::Failed);
+    }
+
+    let entry = Directory::open(dirname(&path))
+        .and_then(|parent| parent.find(filename(&path)))
+        .ok_or(FsError::Failed)?;
+
+    Ok(with_fs(dir.mount, |fs| fs.remove(&entry))?)
+}
+
+/// Deletes the file at `path`.
+pub(crate) fn unlink(path: &str) -> Result<(), FsError> {
+    let path = canonicalize(path, false).ok_or(FsError::Failed)?;
+    check_access(dirname(&path), Access::Write)?;
+
+    let entry = Directory::open(dirname(&path))
+        .and_then(|parent| parent.find(filename(&path)))
+        .ok_or(FsError::Failed)?;
+
+    if entry.is_dir() {
+        return Err(FsError::Failed);
+    }
+
+    let last_link = !matches!(entry.metadata(), Some(meta) if meta.links() > 1);
+    with_fs(entry.dir.mount, |fs| fs.remove(&entry))?;
+
+    // handles still open on a removed FIFO keep its buffer alive
+    if entry.is_fifo() && last_link {
+        pipe::remove_fifo(entry.fifo_id());
+    }
+
+    Ok(())
+}
+
+/// Moves the node at `old` to `new`, which must not exist and be on the same filesystem.
+pub(crate) fn rename(old: &str, new: &str) -> Result<(), FsError> {
+    let (Some(old), Some(new)) = (canonicalize(old, false), canonicalize(new, false)) else {
+        return Err(FsError::Failed);
+    };
+
+    check_access(dirname(&old), Access::Write)?;
+    check_access(dirname(&new), Access::Write)?;
+
+    // a directory can't go inside itself and mount points stay where they are
+    if new.starts_with(&format!("{}/", old))
+        || matches!(Directory::open_resolved(&old), Some(dir) if dir.is_root())
+        || MOUNTS
+            .lock()
+            .iter()
+            .any(|m| m.path.starts_with(&format!("{}/", old)))
+    {
+        return Err(FsError::Failed);
+    }
+
+    let entry = Directory::open(dirname(&old))
+        .and_then(|parent| parent.find(filename(&old)))
+        .ok_or(FsError::Failed)?;
+
+    let mut dir = Directory::open(dirname(&new)).ok_or(FsError::Failed)?;
+    let name = filename(&new);
+
+    if old == new {
+        return Ok(());
+    }
+
+    if dir.mount != entry.dir.mount || name.is_empty() || dir.find(name).is_some() {
+        return Err(FsError::Failed);
+    }
+
+    Ok(with_fs(dir.mount, |fs| fs.rename(&entry, &mut dir, name))?)
+}
+
+/// Creates a symbolic link at `path` pointing to `target`, which doesn't have to exist.
+pub(crate) fn symlink(target: &str, path: &str) -> Result<(), FsError> {
+    let path = canonicalize(path, false).ok_or(FsError::Failed)?;
+    check_access(dirname(&path), Access::Write)?;
+
+    if target.is_empty() {
+        return Err(FsError::Failed);
+    }
+
+    let entry = Directory::open(dirname(&path))
+        .and_then(|mut dir| dir.create_entry(FileKind::Link, filename(&path)))
+        .ok_or(FsError::Failed)?;
+
+    match entry.clone().into_file().write(target.as_bytes()) {
+        Ok(n) if n == target.len() => Ok(()),
+        _ => {
+            with_fs(entry.dir.mount, |fs| fs.remove(&entry)).ok();
+            Err(FsError::Failed)
+        }
+    }
+}
+
+/// Creates a FIFO at `path`, a node opened as one end of a pipe that lasts until it is removed.
+///
+/// Unlike anonymous pipes, either end can be opened while the other one is not, reading then
+/// finds nothing and writing fails until both are.
+pub(crate) fn mkfifo(path: &str) -> Result<(), FsError> {
+    let path = canonicalize(path, false).ok_or(FsError::Failed)?;
+    check_access(dirname(&path), Access::Write)?;
+
+    Directory::open(dirname(&path))
+        .and_then(|mut dir| dir.create_entry(FileKind::Fifo, filename(&path)))
+        .map(|_| ())
+        .ok_or(FsError::Failed)
+}
+
+/// The target of the symbolic link at `path`.
+pub(crate) fn readlink(path: &str) -> Result<String, FsError> {
+    let path = canonicalize(path, false).ok_or(FsError::Failed)?;
+    check_path(&path)?;
+
+    Directory::open(dirname(&path))
+        .and_then(|parent| parent.find(filename(&path)))
+        .and_then(|entry| entry.link_target())
+        .ok_or(FsError::Failed)
+}
+
+/// Gives the node at `old` another name, `new`, which must not exist and be on the same
+/// filesystem. Directories can't be linked.
+pub(crate) fn link(old: &str, new: &str) -> Result<(), FsError> {
+    let (Some(old), Some(new)) = (canonicalize(old, false), canonicalize(new, false)) else {
+        return Err(FsError::Failed);
+    };
+
+    check_path(&old)?;
+    check_access(dirname(&new), Access::Write)?;
+
+    let entry = Directory::open(dirname(&old))
+        .and_then(|parent| parent.find(filename(&old)))
+        .ok_or(FsError::Failed)?;
+
+    let mut dir = Directory::open(dirname(&new)).ok_or(FsError::Failed)?;
+    let name = filename(&new);
+
+    if entry.is_dir() || dir.mount != entry.dir.mount || name.is_empty() || dir.find(name).is_some()
+    {
+        return Err(FsError::Failed);
+    }
+
+    Ok(with_fs(dir.mount, |fs| fs.link(&entry, &mut dir, name))?)
+}
+
+/// The offset `offset` bytes away from the start, the current offset or the end, as told by
+/// `flag`, which must be a single flag. Seeking before the start is an error, seeking past the
+/// end is not.
+pub(crate) fn seek_offset(
+    current: u64,
+    size: u64,
+    offset: isize,
+    flag: SeekFlag,
+) -> Result<u64, ()> {
+    let base = match flag {
+        SeekFlag::START => 0,
+        SeekFlag::CURRENT => current,
+        SeekFlag::END => size,
+        _ => return Err(()),
+    };
+
+    let res = if offset < 0 {
+        base.checked_sub(offset.unsigned_abs() as u64)
+    } else {
+        base.checked_add(offset as u64)
+    };
+
+    res.ok_or(())
+}
+
+/// Opens the node at `path` as told by `flags`, a combination of `OpenFlag`.
+///
+/// Reading and writing need the matching permission on the node, while creating a node needs
+/// the permission to write in its directory.
+pub(crate) fn open(path: &str, flags: usize) -> Result<Resource, FsError> {
+    let open_flag = OpenFlag::from_bits(flags as u8).ok_or(FsError::Failed)?;
+    let path = canonicalize(path, true).ok_or(FsError::Failed)?;
+
+    check_path(&path)?;
+
+    // creating the node when it is missing
+    let create = |path: &str| {
+        if !open_flag.contains(OpenFlag::CREATE) {
+            return Err(FsError::Failed);
+        }
+
+        check_access(dirname(path), Access::Write)
+    };
+
+    // the permissions a node must grant for the flags
+    let check_flags = |meta: Option<Metadata>| -> Result<(), FsError> {
+        let meta = meta.ok_or(FsError::Failed)?;
+
+        if open_flag.contains(OpenFlag::READ) {
+            meta.check(Access::Read)?;
+        }
+
+        if open_flag.intersects(OpenFlag::WRITE | OpenFlag::APPEND | OpenFlag::TRUNCATE) {
+            meta.check(Access::Write)?;
+        }
+
+        Ok(())
+    };
 
     if open_flag.contains(OpenFlag::DIR) {
-        todo!();
+        let dir = match Directory::open(&path) {
+            Some(dir) => {
+                dir.metadata().ok_or(FsError::Failed)?.check(Access::Read)?;
+                dir
+            }
+            None => {
+                create(&path)?;
+                Directory::create(&path).ok_or(FsError::Failed)?
+            }
+        };
+
+        Ok(Resource::Directory(dir))
     } else if open_flag.contains(OpenFlag::DEVICE) {
-        todo!();
+        let device = Resource::Device(devfs::open(&path).ok_or(FsError::Failed)?);
+        check_flags(metadata(&path))?;
+
+        device.share();
+        Ok(device)
     } else {
         // we are opening a file
 
         if !(open_flag.contains(OpenFlag::READ) || open_flag.contains(OpenFlag::WRITE)) {
-            return None;
+            return Err(FsError::Failed);
         }
 
-        let mut file = File::open(path);
+        // device nodes can be opened without the flag as well
+        if let Some(device) = devfs::open(&path) {
+            check_flags(metadata(&path))?;
+
+            let device = Resource::Device(device);
+            device.share();
+            return Ok(device);
+        }
+
+        // a FIFO is opened as the end of its pipe matching the flags
+        let fifo = Directory::open(dirname(&path))
+            .and_then(|parent| parent.find(filename(&path)))
+            .filter(DirEntry::is_fifo);
+
+        if let Some(entry) = fifo {
+            check_flags(entry.metadata())?;
+
+            let write = match open_flag & OpenFlag::READWRITE {
+                OpenFlag::READ => false,
+                OpenFlag::WRITE => true,
+                _ => return Err(FsError::Failed),
+            };
+
+            return Ok(Resource::Pipe(pipe::open_fifo(entry.fifo_id(), write)));
+        }
+
+        let mut file = match File::open(&path) {
+            Some(file) => {
+                check_flags(file.metadata())?;
+                file
+            }
+            None => {
+                create(&path)?;
+                File::create(&path).ok_or(FsError::Failed)?
+            }
+        };
+
+        if open_flag.contains(OpenFlag::TRUNCATE) {
+            file.truncate()?;
+        }
+
+        if open_flag.contains(OpenFlag::APPEND) {
+            file.offset = file.size;
+        }
 
-        todo!();
+        Ok(Resource::File(file))
     }
 }
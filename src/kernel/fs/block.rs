@@ -0,0 +1,138 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+
+pub(crate) const BLOCK_SIZE: usize = 512;
+
+/// A storage device addressed in fixed size blocks.
+///
+/// Filesystems never talk to hardware directly, they only go through this trait
+/// (usually behind a `BlockCache`).
+pub(crate) trait BlockDevice: Send {
+    fn block_size(&self) -> usize;
+    fn block_count(&self) -> u32;
+    fn read_block(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), ()>;
+    fn write_block(&mut self, addr: u32, buf: &[u8]) -> Result<(), ()>;
+}
+
+/// A block device backed by kernel memory, used when no disk is available.
+pub(crate) struct MemDisk {
+    data: Vec<u8>,
+}
+
+impl MemDisk {
+    pub(crate) fn new(block_count: u32) -> Self {
+        Self {
+            data: vec![0; block_count as usize * BLOCK_SIZE],
+        }
+    }
+}
+
+impl BlockDevice for MemDisk {
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn block_count(&self) -> u32 {
+        (self.data.len() / BLOCK_SIZE) as u32
+    }
+
+    fn read_block(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), ()> {
+        let start = addr as usize * BLOCK_SIZE;
+        let end = start + BLOCK_SIZE;
+
+        if end > self.data.len() || buf.len() < BLOCK_SIZE {
+            return Err(());
+        }
+
+        buf[..BLOCK_SIZE].copy_from_slice(&self.data[start..end]);
+        Ok(())
+    }
+
+    fn write_block(&mut self, addr: u32, buf: &[u8]) -> Result<(), ()> {
+        let start = addr as usize * BLOCK_SIZE;
+        let end = start + BLOCK_SIZE;
+
+        if end > self.data.len() || buf.len() < BLOCK_SIZE {
+            return Err(());
+        }
+
+        self.data[start..end].copy_from_slice(&buf[..BLOCK_SIZE]);
+        Ok(())
+    }
+}
+
+/// A contiguous range of blocks of another device, seen as a device of its own.
+pub(crate) struct Partition {
+    dev: Box<dyn BlockDevice>,
+    start: u32,
+    count: u32,
+}
+
+impl Partition {
+    pub(crate) fn new(dev: Box<dyn BlockDevice>, start: u32, count: u32) -> Self {
+        Self { dev, start, count }
+    }
+}
+
+impl BlockDevice for Partition {
+    fn block_size(&self) -> usize {
+        self.dev.block_size()
+    }
+
+    fn block_count(&self) -> u32 {
+        self.count
+    }
+
+    fn read_block(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), ()> {
+        if addr >= self.count {
+            return Err(());
+        }
+
+        self.dev.read_block(self.start + addr, buf)
+    }
+
+    fn write_block(&mut self, addr: u32, buf: &[u8]) -> Result<(), ()> {
+        if addr >= self.count {
+            return Err(());
+        }
+
+        self.dev.write_block(self.start + addr, buf)
+    }
+}
+
+/// A copy of a single block of a device.
+#[derive(Clone)]
+pub(crate) struct Block {
+    addr: u32,
+    buf: [u8; BLOCK_SIZE],
+}
+
+impl Block {
+    pub(crate) fn new(addr: u32) -> Self {
+        Self {
+            addr,
+            buf: [0; BLOCK_SIZE],
+        }
+    }
+
+    pub(crate) fn read(dev: &mut dyn BlockDevice, addr: u32) -> Result<Self, ()> {
+        let mut block = Self::new(addr);
+        dev.read_block(addr, &mut block.buf)?;
+        Ok(block)
+    }
+
+    pub(crate) fn write(&self, dev: &mut dyn BlockDevice) -> Result<(), ()> {
+        dev.write_block(self.addr, &self.buf)
+    }
+
+    pub(crate) const fn addr(&self) -> u32 {
+        self.addr
+    }
+
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub(crate) fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
@@ -0,0 +1,142 @@
+use super::block::{BlockDevice, BLOCK_SIZE};
+use alloc::{boxed::Box, collections::BTreeMap};
+
+/// Blocks the filesystems keep in memory through their `BlockCache`.
+pub(crate) const CACHE_CAPACITY: usize = 256;
+
+struct CachedBlock {
+    buf: [u8; BLOCK_SIZE],
+    dirty: bool,
+    last_used: u64,
+}
+
+/// Write-back LRU cache sitting in front of a block device.
+///
+/// Writes only touch the cached copy and mark it dirty, the device is updated when
+/// the block gets evicted or when `sync` is called.
+pub(crate) struct BlockCache {
+    dev: Box<dyn BlockDevice>,
+    capacity: usize,
+    clock: u64,
+    blocks: BTreeMap<u32, CachedBlock>,
+}
+
+impl BlockCache {
+    pub(crate) fn new(dev: Box<dyn BlockDevice>, capacity: usize) -> Self {
+        Self {
+            dev,
+            capacity,
+            clock: 0,
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    fn evict(&mut self) -> Result<(), ()> {
+        let Some(addr) = self
+            .blocks
+            .iter()
+            .min_by_key(|(_, block)| block.last_used)
+            .map(|(addr, _)| *addr)
+        else {
+            return Ok(());
+        };
+
+        if let Some(block) = self.blocks.get(&addr) {
+            if block.dirty {
+                self.dev.write_block(addr, &block.buf)?;
+            }
+        }
+
+        self.blocks.remove(&addr);
+        Ok(())
+    }
+
+    fn load(&mut self, addr: u32, fetch: bool) -> Result<&mut CachedBlock, ()> {
+        if addr >= self.dev.block_count() {
+            return Err(());
+        }
+
+        self.clock += 1;
+
+        if !self.blocks.contains_key(&addr) {
+            if self.blocks.len() >= self.capacity {
+                self.evict()?;
+            }
+
+            let mut buf = [0; BLOCK_SIZE];
+
+            if fetch {
+                self.dev.read_block(addr, &mut buf)?;
+            }
+
+            let block = CachedBlock {
+                buf,
+                dirty: false,
+                last_used: 0,
+            };
+
+            self.blocks.insert(addr, block);
+        }
+
+        let block = self.blocks.get_mut(&addr).ok_or(())?;
+        block.last_used = self.clock;
+        Ok(block)
+    }
+
+    /// Writes every dirty block back to the device.
+    pub(crate) fn sync(&mut self) -> Result<(), ()> {
+        for (addr, block) in self.blocks.iter_mut() {
+            if block.dirty {
+                self.dev.write_block(*addr, &block.buf)?;
+                block.dirty = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drops every cached block after writing back the dirty ones.
+    pub(crate) fn flush(&mut self) -> Result<(), ()> {
+        self.sync()?;
+        self.blocks.clear();
+        Ok(())
+    }
+
+    /// Gives back the underlying device, dirty blocks are written first.
+    pub(crate) fn into_inner(mut self) -> Box<dyn BlockDevice> {
+        self.sync().ok();
+        self.dev
+    }
+}
+
+impl BlockDevice for BlockCache {
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn block_count(&self) -> u32 {
+        self.dev.block_count()
+    }
+
+    fn read_block(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), ()> {
+        if buf.len() < BLOCK_SIZE {
+            return Err(());
+        }
+
+        let block = self.load(addr, true)?;
+        buf[..BLOCK_SIZE].copy_from_slice(&block.buf);
+        Ok(())
+    }
+
+    fn write_block(&mut self, addr: u32, buf: &[u8]) -> Result<(), ()> {
+        if buf.len() < BLOCK_SIZE {
+            return Err(());
+        }
+
+        // the whole block is overwritten so there is no need to fetch it first
+        let block = self.load(addr, false)?;
+        block.buf.copy_from_slice(&buf[..BLOCK_SIZE]);
+        block.dirty = true;
+        Ok(())
+    }
+}
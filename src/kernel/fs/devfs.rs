@@ -0,0 +1,81 @@
+use super::{dirname, filename, DirEntry, Directory, File, FileKind, FileSystem};
+use crate::kernel::{
+    io::console::{Console, VT_COUNT},
+    resource::Device,
+};
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use spin::Mutex;
+
+static DEVICES: Mutex<BTreeMap<String, Device>> = Mutex::new(BTreeMap::new());
+
+/// Registers the devices that are always there, drivers add theirs with `register`.
+pub(crate) fn init() {
+    register("null", Device::Null);
+    register("zero", Device::Zero);
+    register("random", Device::Random);
+    register("console", Device::Console(Console::new()));
+
+    for vt in 0..VT_COUNT {
+        register(
+            &format!("tty{}", vt + 1),
+            Device::Console(Console::with_vt(vt)),
+        );
+    }
+
+    register("ttyS0", Device::Serial);
+}
+
+/// Makes `device` available as `/dev/<name>`, replacing any device of the same name.
+pub(crate) fn register(name: &str, device: Device) {
+    DEVICES.lock().insert(name.into(), device);
+}
+
+pub(crate) fn unregister(name: &str) {
+    DEVICES.lock().remove(name);
+}
+
+/// A fresh instance of the device registered as `name`.
+pub(crate) fn device(name: &str) -> Option<Device> {
+    DEVICES.lock().get(name).cloned()
+}
+
+/// The device behind the node at `path`, which must be absolute.
+pub(crate) fn open(path: &str) -> Option<Device> {
+    let entry = Directory::open(dirname(path))?.find(filename(path))?;
+
+    match entry.kind() {
+        FileKind::Device => device(entry.name()),
+        _ => None,
+    }
+}
+
+/// Lists the registered devices, usually mounted on `/dev`.
+pub(crate) struct DevFs;
+
+impl FileSystem for DevFs {
+    fn root(&self) -> u32 {
+        0
+    }
+
+    fn entries(&mut self, dir: &Directory) -> Result<Vec<DirEntry>, ()> {
+        let entries = DEVICES
+            .lock()
+            .keys()
+            .enumerate()
+            .map(|(i, name)| DirEntry {
+                dir: dir.clone(),
+                name: name.clone(),
+                addr: i as u32 + 1,
+                size: 0,
+                kind: FileKind::Device,
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    // device nodes are opened as devices, never as files
+    fn read(&mut self, file: &File, buf: &mut [u8]) -> Result<usize, ()> {
+        Err(())
+    }
+}
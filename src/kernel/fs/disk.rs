@@ -0,0 +1,225 @@
+use super::{
+    block::{Block, BlockDevice, BLOCK_SIZE},
+    devfs, partition, seek_offset, FileIO, SeekFlag,
+};
+use crate::kernel::{
+    io::{ata, kprint},
+    resource::Device,
+};
+use alloc::{format, string::String, vec::Vec};
+use spin::Mutex;
+
+static DISKS: Mutex<Vec<Disk>> = Mutex::new(Vec::new());
+
+// the disks a mounted filesystem lives on, by name
+static MOUNTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Registers every drive along with each of its partitions.
+pub(crate) fn init() {
+    let mut disks = DISKS.lock();
+
+    for drive in ata::drives() {
+        let name = drive.name();
+        let count = drive.block_count();
+
+        let mut whole = Disk::new(name.clone(), drive.clone(), 0, count);
+        let partitions = partition::scan(&mut whole);
+
+        devfs::register(&name, Device::Disk(whole.clone()));
+        disks.push(whole);
+
+        for p in partitions {
+            let disk = Disk::new(
+                format!("{}p{}", name, p.number),
+                drive.clone(),
+                p.start,
+                p.count,
+            );
+
+            kprint!(
+                "{}: {} KiB at block {}\n",
+                disk.name,
+                p.count as usize * BLOCK_SIZE / 1024,
+                p.start
+            );
+
+            devfs::register(&disk.name, Device::Disk(disk.clone()));
+            disks.push(disk);
+        }
+    }
+}
+
+/// Every registered disk, drives come right before their partitions.
+pub(crate) fn disks() -> Vec<Disk> {
+    DISKS.lock().clone()
+}
+
+pub(crate) fn find(name: &str) -> Option<Disk> {
+    DISKS.lock().iter().find(|disk| disk.name == name).cloned()
+}
+
+/// Records whether a filesystem living on the disk `name` is mounted.
+pub(crate) fn set_mounted(name: &str, mounted: bool) {
+    let mut disks = MOUNTED.lock();
+    disks.retain(|disk| disk != name);
+
+    if mounted {
+        disks.push(name.into());
+    }
+}
+
+/// A whole drive or one of its partitions.
+///
+/// It can be handed to a filesystem as a block device or opened as a resource, in which case
+/// it is read and written like a file.
+#[derive(Debug, Clone)]
+pub(crate) struct Disk {
+    name: String,
+    drive: ata::Drive,
+    start: u32,
+    count: u32,
+    offset: u64,
+}
+
+impl Disk {
+    fn new(name: String, drive: ata::Drive, start: u32, count: u32) -> Self {
+        Self {
+            name,
+            drive,
+            start,
+            count,
+            offset: 0,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn is_partition(&self) -> bool {
+        self.name != self.drive.name()
+    }
+
+    /// Whether other registered disks are partitions of this one.
+    pub(crate) fn has_partitions(&self) -> bool {
+        !self.is_partition()
+            && DISKS
+                .lock()
+                .iter()
+                .any(|d| d.is_partition() && d.drive.name() == self.name)
+    }
+
+    /// Whether a mounted filesystem lives on this disk, or on one sharing some of its blocks.
+    pub(crate) fn is_mounted(&self) -> bool {
+        let mounted = MOUNTED.lock();
+
+        DISKS.lock().iter().any(|d| {
+            mounted.contains(&d.name)
+                && d.drive.name() == self.drive.name()
+                && d.start < self.start + self.count
+                && self.start < d.start + d.count
+        })
+    }
+
+    pub(crate) const fn size(&self) -> u64 {
+        self.count as u64 * BLOCK_SIZE as u64
+    }
+
+    /// Moves the offset used when the disk is read like a file, which can't go past its end.
+    pub(crate) fn seek(&mut self, offset: isize, flag: SeekFlag) -> Result<usize, ()> {
+        let offset = seek_offset(self.offset, self.size(), offset, flag)?;
+
+        if offset > self.size() {
+            return Err(());
+        }
+
+        self.offset = offset;
+        Ok(offset as usize)
+    }
+}
+
+impl BlockDevice for Disk {
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn block_count(&self) -> u32 {
+        self.count
+    }
+
+    fn read_block(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), ()> {
+        if addr >= self.count {
+            return Err(());
+        }
+
+        self.drive.read_block(self.start + addr, buf)
+    }
+
+    fn write_block(&mut self, addr: u32, buf: &[u8]) -> Result<(), ()> {
+        if addr >= self.count {
+            return Err(());
+        }
+
+        self.drive.write_block(self.start + addr, buf)
+    }
+}
+
+impl FileIO for Disk {
+    /// Reads the blocks as the mounted filesystems left them, their cached changes being
+    /// written back first.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        if self.is_mounted() {
+            super::sync()?;
+        }
+
+        let len = buf
+            .len()
+            .min(self.size().saturating_sub(self.offset) as usize);
+        let mut n = 0;
+
+        while n < len {
+            let addr = (self.offset / BLOCK_SIZE as u64) as u32;
+            let i = (self.offset % BLOCK_SIZE as u64) as usize;
+            let count = (BLOCK_SIZE - i).min(len - n);
+            let block = Block::read(self, addr)?;
+
+            buf[n..n + count].copy_from_slice(&block.data()[i..i + count]);
+            n += count;
+            self.offset += count as u64;
+        }
+
+        Ok(n)
+    }
+
+    /// Fails while a filesystem is mounted on the disk, its cache would overwrite the blocks
+    /// written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, ()> {
+        if self.is_mounted() {
+            return Err(());
+        }
+
+        let len = buf
+            .len()
+            .min(self.size().saturating_sub(self.offset) as usize);
+        let mut n = 0;
+
+        while n < len {
+            let addr = (self.offset / BLOCK_SIZE as u64) as u32;
+            let i = (self.offset % BLOCK_SIZE as u64) as usize;
+            let count = (BLOCK_SIZE - i).min(len - n);
+
+            // partial blocks have to be read first
+            let mut block = match count {
+                BLOCK_SIZE => Block::new(addr),
+                _ => Block::read(self, addr)?,
+            };
+
+            block.data_mut()[i..i + count].copy_from_slice(&buf[n..n + count]);
+            block.write(self)?;
+            n += count;
+            self.offset += count as u64;
+        }
+
+        Ok(n)
+    }
+}
@@ -0,0 +1,374 @@
+use super::{
+    block::{Block, BlockDevice, BLOCK_SIZE},
+    cache::{BlockCache, CACHE_CAPACITY},
+    DirEntry, Directory, File, FileKind, FileSystem, Metadata,
+};
+use crate::kernel::process::ProcessPrivileges;
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+
+// the superblock always starts 1024 bytes into the device, whatever the block size
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const MAGIC: u16 = 0xEF53;
+
+const ROOT_INODE: u32 = 2;
+const GOOD_OLD_INODE_SIZE: u16 = 128;
+const GROUP_DESC_SIZE: u64 = 32;
+
+// incompatible features we know how to read, anything else is refused
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+const INCOMPAT_FLEX_BG: u32 = 0x0200;
+const INCOMPAT_SUPPORTED: u32 = INCOMPAT_FILETYPE | INCOMPAT_FLEX_BG;
+
+const MODE_TYPE_MASK: u16 = 0xF000;
+const MODE_FIFO: u16 = 0x1000;
+const MODE_DIR: u16 = 0x4000;
+const MODE_FILE: u16 = 0x8000;
+const MODE_SYMLINK: u16 = 0xA000;
+
+// `i_block` holds 12 direct block pointers followed by a single, double and triple indirect one
+const DIRECT_BLOCKS: u32 = 12;
+const INODE_BLOCK_OFFSET: usize = 40;
+const INODE_BLOCK_LEN: usize = 60;
+
+// integers of ext2 are stored little endian
+
+fn get_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+struct Inode {
+    mode: u16,
+    uid: u32,
+    gid: u32,
+    size: u32,
+    accessed: u32,
+    modified: u32,
+    sector_count: u32,
+    block: [u8; INODE_BLOCK_LEN],
+}
+
+impl Inode {
+    fn kind(&self) -> u16 {
+        self.mode & MODE_TYPE_MASK
+    }
+
+    fn block_ptr(&self, i: usize) -> u32 {
+        get_u32(&self.block, i * 4)
+    }
+}
+
+/// A read-only ext2 filesystem, such as the images built by `mke2fs -d`.
+///
+/// Symbolic links are resolved while listing a directory, they show up as the file or the
+/// directory they point to.
+pub(crate) struct Ext2Fs {
+    cache: BlockCache,
+    block_size: u32,
+    inode_size: u32,
+    inodes_per_group: u32,
+    inode_count: u32,
+    group_count: u32,
+    // first block of the group descriptor table
+    gdt_block: u32,
+    filetype: bool,
+}
+
+impl Ext2Fs {
+    /// Mounts the ext2 filesystem stored on `dev`, giving the device back if there is none.
+    pub(crate) fn open(dev: Box<dyn BlockDevice>) -> Result<Self, Box<dyn BlockDevice>> {
+        let mut fs = Self {
+            cache: BlockCache::new(dev, CACHE_CAPACITY),
+            block_size: 1024,
+            inode_size: GOOD_OLD_INODE_SIZE as u32,
+            inodes_per_group: 0,
+            inode_count: 0,
+            group_count: 0,
+            gdt_block: 0,
+            filetype: false,
+        };
+
+        match fs.read_superblock() {
+            Some(()) => Ok(fs),
+            None => Err(fs.cache.into_inner()),
+        }
+    }
+
+    fn read_superblock(&mut self) -> Option<()> {
+        let mut sb = [0; 1024];
+        self.read_bytes(SUPERBLOCK_OFFSET, &mut sb).ok()?;
+
+        let block_count = get_u32(&sb, 4);
+        let first_data_block = get_u32(&sb, 20);
+        let log_block_size = get_u32(&sb, 24);
+        let rev_level = get_u32(&sb, 76);
+        let incompat = get_u32(&sb, 96);
+
+        if get_u16(&sb, 56) != MAGIC || log_block_size > 6 || incompat & !INCOMPAT_SUPPORTED != 0 {
+            return None;
+        }
+
+        // revision 0 has fixed size inodes and no feature flags
+        if rev_level >= 1 {
+            self.inode_size = get_u16(&sb, 88) as u32;
+            self.filetype = incompat & INCOMPAT_FILETYPE != 0;
+        }
+
+        self.block_size = 1024 << log_block_size;
+        self.inode_count = get_u32(&sb, 0);
+        self.inodes_per_group = get_u32(&sb, 40);
+        self.gdt_block = first_data_block + 1;
+
+        let device_size = self.cache.block_count() as u64 * BLOCK_SIZE as u64;
+
+        if self.inodes_per_group == 0
+            || self.inode_size < GOOD_OLD_INODE_SIZE as u32
+            || block_count as u64 * self.block_size as u64 > device_size
+        {
+            return None;
+        }
+
+        // every group holds the same number of inodes
+        self.group_count = self.inode_count / self.inodes_per_group;
+        Some(())
+    }
+
+    // reads `buf.len()` bytes starting `offset` bytes into the device
+    fn read_bytes(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), ()> {
+        let mut n = 0;
+
+        while n < buf.len() {
+            let pos = offset + n as u64;
+            let i = (pos % BLOCK_SIZE as u64) as usize;
+            let len = (BLOCK_SIZE - i).min(buf.len() - n);
+
+            let block = Block::read(&mut self.cache, (pos / BLOCK_SIZE as u64) as u32)?;
+            buf[n..n + len].copy_from_slice(&block.data()[i..i + len]);
+            n += len;
+        }
+
+        Ok(())
+    }
+
+    fn block_offset(&self, block: u32) -> u64 {
+        block as u64 * self.block_size as u64
+    }
+
+    fn read_inode(&mut self, n: u32) -> Result<Inode, ()> {
+        if n == 0 || n > self.inode_count {
+            return Err(());
+        }
+
+        let group = (n - 1) / self.inodes_per_group;
+        let index = (n - 1) % self.inodes_per_group;
+
+        if group >= self.group_count {
+            return Err(());
+        }
+
+        let mut desc = [0; GROUP_DESC_SIZE as usize];
+        let desc_offset = self.block_offset(self.gdt_block) + group as u64 * GROUP_DESC_SIZE;
+        self.read_bytes(desc_offset, &mut desc)?;
+
+        let table = get_u32(&desc, 8);
+        let mut raw = [0; GOOD_OLD_INODE_SIZE as usize];
+        let offset = self.block_offset(table) + index as u64 * self.inode_size as u64;
+        self.read_bytes(offset, &mut raw)?;
+
+        let mut block = [0; INODE_BLOCK_LEN];
+        block.copy_from_slice(&raw[INODE_BLOCK_OFFSET..INODE_BLOCK_OFFSET + INODE_BLOCK_LEN]);
+
+        Ok(Inode {
+            mode: get_u16(&raw, 0),
+            // the high halves of the IDs are kept in the OS specific area by Linux
+            uid: (get_u16(&raw, 120) as u32) << 16 | get_u16(&raw, 2) as u32,
+            gid: (get_u16(&raw, 122) as u32) << 16 | get_u16(&raw, 24) as u32,
+            size: get_u32(&raw, 4),
+            accessed: get_u32(&raw, 8),
+            modified: get_u32(&raw, 16),
+            sector_count: get_u32(&raw, 28),
+            block,
+        })
+    }
+
+    // the `i`th entry of the block of pointers `block`
+    fn read_ptr(&mut self, block: u32, i: u32) -> Result<u32, ()> {
+        if block == 0 {
+            return Ok(0);
+        }
+
+        let mut buf = [0; 4];
+        self.read_bytes(self.block_offset(block) + i as u64 * 4, &mut buf)?;
+        Ok(get_u32(&buf, 0))
+    }
+
+    /// Address of the `i`th block of the inode, 0 for a hole.
+    fn map_block(&mut self, inode: &Inode, i: u32) -> Result<u32, ()> {
+        let ptrs = self.block_size / 4;
+
+        if i < DIRECT_BLOCKS {
+            return Ok(inode.block_ptr(i as usize));
+        }
+
+        let i = i - DIRECT_BLOCKS;
+
+        if i < ptrs {
+            return self.read_ptr(inode.block_ptr(12), i);
+        }
+
+        let i = i - ptrs;
+
+        if i < ptrs * ptrs {
+            let block = self.read_ptr(inode.block_ptr(13), i / ptrs)?;
+            return self.read_ptr(block, i % ptrs);
+        }
+
+        let i = i - ptrs * ptrs;
+        let block = self.read_ptr(inode.block_ptr(14), i / (ptrs * ptrs))?;
+        let block = self.read_ptr(block, i / ptrs % ptrs)?;
+        self.read_ptr(block, i % ptrs)
+    }
+
+    fn read_inode_data(&mut self, inode: &Inode, offset: u32, buf: &mut [u8]) -> Result<usize, ()> {
+        if offset >= inode.size {
+            return Ok(0);
+        }
+
+        let len = buf.len().min((inode.size - offset) as usize);
+        let mut n = 0;
+
+        while n < len {
+            let pos = offset + n as u32;
+            let i = pos % self.block_size;
+            let count = ((self.block_size - i) as usize).min(len - n);
+
+            match self.map_block(inode, pos / self.block_size)? {
+                0 => buf[n..n + count].fill(0),
+                block => {
+                    self.read_bytes(self.block_offset(block) + i as u64, &mut buf[n..n + count])?
+                }
+            }
+
+            n += count;
+        }
+
+        Ok(n)
+    }
+
+    // (inode, name, file type) of every entry of a directory inode
+    fn dir_records(&mut self, inode: &Inode) -> Result<Vec<(u32, String, u8)>, ()> {
+        let mut data = vec![0; inode.size as usize];
+        self.read_inode_data(inode, 0, &mut data)?;
+
+        let mut records = Vec::new();
+        let mut pos = 0;
+
+        while pos + 8 <= data.len() {
+            let ino = get_u32(&data, pos);
+            let rec_len = get_u16(&data, pos + 4) as usize;
+
+            // without the filetype feature the name length is 16 bits wide
+            let (name_len, file_type) = if self.filetype {
+                (data[pos + 6] as usize, data[pos + 7])
+            } else {
+                (get_u16(&data, pos + 6) as usize, 0)
+            };
+
+            if rec_len < 8 || pos + 8 + name_len > data.len() {
+                break;
+            }
+
+            if ino != 0 {
+                let name = String::from_utf8_lossy(&data[pos + 8..pos + 8 + name_len]).into();
+                records.push((ino, name, file_type));
+            }
+
+            pos += rec_len;
+        }
+
+        Ok(records)
+    }
+
+    fn read_symlink(&mut self, inode: &Inode) -> Result<Vec<u8>, ()> {
+        // short targets are stored in place of the block pointers
+        if inode.sector_count == 0 && (inode.size as usize) < INODE_BLOCK_LEN {
+            return Ok(inode.block[..inode.size as usize].to_vec());
+        }
+
+        let mut buf = vec![0; inode.size as usize];
+        self.read_inode_data(inode, 0, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl FileSystem for Ext2Fs {
+    fn root(&self) -> u32 {
+        ROOT_INODE
+    }
+
+    fn entries(&mut self, dir: &Directory) -> Result<Vec<DirEntry>, ()> {
+        let dir_inode = self.read_inode(dir.addr)?;
+        let mut entries = Vec::new();
+
+        for (ino, name, _) in self.dir_records(&dir_inode)? {
+            if name == "." || name == ".." {
+                continue;
+            }
+
+            let Ok(inode) = self.read_inode(ino) else {
+                continue;
+            };
+
+            // links are followed by the VFS
+            let kind = match inode.kind() {
+                MODE_DIR => FileKind::Dir,
+                MODE_FILE => FileKind::File,
+                MODE_FIFO => FileKind::Fifo,
+                MODE_SYMLINK => FileKind::Link,
+                _ => continue,
+            };
+
+            entries.push(DirEntry {
+                dir: dir.clone(),
+                name,
+                addr: ino,
+                size: inode.size,
+                kind,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn read(&mut self, file: &File, buf: &mut [u8]) -> Result<usize, ()> {
+        let inode = self.read_inode(file.addr)?;
+
+        if inode.kind() != MODE_SYMLINK {
+            return self.read_inode_data(&inode, file.offset, buf);
+        }
+
+        let target = self.read_symlink(&inode)?;
+        let target = target.get(file.offset as usize..).unwrap_or_default();
+        let n = buf.len().min(target.len());
+
+        buf[..n].copy_from_slice(&target[..n]);
+        Ok(n)
+    }
+
+    // ext2 doesn't record when an inode was created
+    fn metadata(&mut self, entry: &DirEntry, meta: &mut Metadata) -> Result<(), ()> {
+        let inode = self.read_inode(entry.addr)?;
+
+        meta.user = inode.uid as u64;
+        meta.group = inode.gid as u64;
+        meta.permissions = ProcessPrivileges::from_mode(inode.mode as u32, entry.kind).bits();
+        meta.accessed = inode.accessed as u64;
+        meta.modified = inode.modified as u64;
+        Ok(())
+    }
+}
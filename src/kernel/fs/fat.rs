@@ -0,0 +1,852 @@
+use super::{
+    block::{Block, BlockDevice, BLOCK_SIZE},
+    cache::{BlockCache, CACHE_CAPACITY},
+    DirEntry, Directory, File, FileKind, FileSystem, Metadata,
+};
+use crate::kernel::process::ProcessPrivileges;
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
+
+// only the low 28 bits of a FAT32 entry are meaningful
+const CLUSTER_MASK: u32 = 0x0FFF_FFFF;
+const END_OF_CHAIN: u32 = 0x0FFF_FFF8;
+const BAD_CLUSTER: u32 = 0x0FFF_FFF7;
+
+const RECORD_SIZE: usize = 32;
+
+const ATTR_READ_ONLY: u8 = 0x01;
+const ATTR_HIDDEN: u8 = 0x02;
+const ATTR_SYSTEM: u8 = 0x04;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LFN: u8 = ATTR_READ_ONLY | ATTR_HIDDEN | ATTR_SYSTEM | ATTR_VOLUME_ID;
+
+const RECORD_END: u8 = 0x00;
+const RECORD_DELETED: u8 = 0xE5;
+
+// a long name is split in chunks of 13 UCS-2 characters, one per record
+const LFN_CHARS: usize = 13;
+const LFN_LAST: u8 = 0x40;
+const LFN_OFFSETS: [usize; LFN_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+const MAX_NAME_LEN: usize = 255;
+
+// there is no clock yet, new entries are dated 1980-01-01
+const DEFAULT_DATE: u16 = 0x21;
+
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FS_INFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+
+// integers of FAT filesystems are stored little endian
+
+fn get_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn set_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn set_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+// seconds since the Unix epoch of a date and time as stored in a record, 0 if there is no date
+fn timestamp(date: u16, time: u16) -> u64 {
+    let year = 1980 + (date >> 9) as u64;
+    let month = (date >> 5 & 0xF) as u64;
+    let day = (date & 0x1F) as u64;
+
+    if !(1..=12).contains(&month) || day == 0 {
+        return 0;
+    }
+
+    // days since 1970-01-01, counting years from March to put the leap day last
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let days = y * 365 + y / 4 - y / 100 + y / 400 + day_of_year - 719_468;
+
+    let hours = (time >> 11) as u64;
+    let minutes = (time >> 5 & 0x3F) as u64;
+    let seconds = (time & 0x1F) as u64 * 2;
+
+    days * 86400 + hours * 3600 + minutes * 60 + seconds
+}
+
+// checksum of a short name, stored in each of the long name records preceding it
+fn checksum(short_name: &[u8]) -> u8 {
+    short_name.iter().fold(0u8, |sum, &c| {
+        (sum >> 1).wrapping_add(sum << 7).wrapping_add(c)
+    })
+}
+
+// the 8.3 name stored in a record, lowercased when the flags of byte 12 ask for it
+fn decode_short_name(record: &[u8]) -> String {
+    let mut base = record[0..8].to_vec();
+
+    // 0xE5 is a valid first character, it is stored as 0x05 to avoid marking the entry deleted
+    if base[0] == 0x05 {
+        base[0] = RECORD_DELETED;
+    }
+
+    let decode = |bytes: &[u8], lower: bool| -> String {
+        bytes
+            .iter()
+            .map(|&c| c as char)
+            .map(|c| if lower { c.to_ascii_lowercase() } else { c })
+            .collect::<String>()
+            .trim_end()
+            .into()
+    };
+
+    let base = decode(&base, record[12] & 0x08 != 0);
+    let ext = decode(&record[8..11], record[12] & 0x10 != 0);
+
+    if ext.is_empty() {
+        base
+    } else {
+        format!("{}.{}", base, ext)
+    }
+}
+
+fn is_short_char(c: char) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || "!#$%&'()-@^_`{}~".contains(c)
+}
+
+// `name` as an 8.3 name if it can be stored without a long name
+fn exact_short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, ext) = match name.rfind('.') {
+        Some(i) => (&name[..i], &name[i + 1..]),
+        None => (name, ""),
+    };
+
+    if base.is_empty()
+        || base.len() > 8
+        || ext.len() > 3
+        || !base.chars().chain(ext.chars()).all(is_short_char)
+    {
+        return None;
+    }
+
+    let mut res = [b' '; 11];
+    res[..base.len()].copy_from_slice(base.as_bytes());
+    res[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    Some(res)
+}
+
+// the `BASIS~N.EXT` alias of a long name
+fn alias(name: &str, n: usize) -> [u8; 11] {
+    let to_short = |s: &str| -> Vec<u8> {
+        s.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| c.to_ascii_uppercase())
+            .map(|c| if is_short_char(c) { c as u8 } else { b'_' })
+            .collect()
+    };
+
+    let name = name.trim_start_matches('.');
+
+    let (base, ext) = match name.rfind('.') {
+        Some(i) => (to_short(&name[..i]), to_short(&name[i + 1..])),
+        None => (to_short(name), Vec::new()),
+    };
+
+    let tail = format!("~{}", n);
+    let base_len = base.len().min(8 - tail.len());
+
+    let mut res = [b' '; 11];
+    res[..base_len].copy_from_slice(&base[..base_len]);
+    res[base_len..base_len + tail.len()].copy_from_slice(tail.as_bytes());
+
+    let ext_len = ext.len().min(3);
+    res[8..8 + ext_len].copy_from_slice(&ext[..ext_len]);
+    res
+}
+
+/// An entry as stored in a directory, along with its position.
+struct Record {
+    name: String,
+    // byte offset in the directory of the first record of the entry (a long name record if
+    // there is one) and of its short name record
+    start: u32,
+    pos: u32,
+    raw: [u8; RECORD_SIZE],
+}
+
+impl Record {
+    fn cluster(&self) -> u32 {
+        (get_u16(&self.raw, 20) as u32) << 16 | get_u16(&self.raw, 26) as u32
+    }
+
+    fn size(&self) -> u32 {
+        get_u32(&self.raw, 28)
+    }
+
+    fn is_dir(&self) -> bool {
+        self.raw[11] & ATTR_DIRECTORY != 0
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.raw[11] & ATTR_READ_ONLY != 0
+    }
+}
+
+/// The records needed to store a name that isn't in a directory yet.
+struct NewName {
+    short_name: [u8; 11],
+    lfn: Vec<[u8; RECORD_SIZE]>,
+    pos: u32, // where the first record goes
+}
+
+/// A FAT32 filesystem with long file names, as written by mtools or QEMU's `fat:rw:` drives.
+pub(crate) struct FatFs {
+    cache: BlockCache,
+    sectors_per_cluster: u32,
+    fat_start: u32,
+    fat_size: u32,
+    fat_count: u32,
+    data_start: u32,
+    cluster_count: u32,
+    root_cluster: u32,
+    fs_info: u32,
+    // where to start looking for a free cluster
+    next_free: u32,
+    // the free cluster count of the FSInfo sector no longer matches the FAT
+    dirty: bool,
+}
+
+impl FatFs {
+    /// Mounts the FAT32 filesystem stored on `dev`, giving the device back if there is none.
+    pub(crate) fn open(dev: Box<dyn BlockDevice>) -> Result<Self, Box<dyn BlockDevice>> {
+        let mut cache = BlockCache::new(dev, CACHE_CAPACITY);
+
+        let Ok(boot) = Block::read(&mut cache, 0) else {
+            return Err(cache.into_inner());
+        };
+
+        let bpb = boot.data();
+
+        let bytes_per_sector = get_u16(bpb, 11) as usize;
+        let sectors_per_cluster = bpb[13] as u32;
+        let reserved = get_u16(bpb, 14) as u32;
+        let fat_count = bpb[16] as u32;
+        let fat_size_16 = get_u16(bpb, 22);
+        let fat_size = get_u32(bpb, 36);
+
+        let total = match get_u16(bpb, 19) {
+            0 => get_u32(bpb, 32),
+            n => n as u32,
+        };
+
+        // FAT12 and FAT16 have a 16 bits FAT size and a fixed root directory
+        if bpb[510..512] != [0x55, 0xAA]
+            || bytes_per_sector != BLOCK_SIZE
+            || sectors_per_cluster == 0
+            || fat_count == 0
+            || fat_size_16 != 0
+            || fat_size == 0
+        {
+            return Err(cache.into_inner());
+        }
+
+        let data_start = reserved + fat_count * fat_size;
+
+        if total <= data_start || total > cache.block_count() {
+            return Err(cache.into_inner());
+        }
+
+        let fs = Self {
+            sectors_per_cluster,
+            fat_start: reserved,
+            fat_size,
+            fat_count,
+            data_start,
+            cluster_count: (total - data_start) / sectors_per_cluster,
+            root_cluster: get_u32(bpb, 44),
+            fs_info: get_u16(bpb, 48) as u32,
+            next_free: 2,
+            dirty: false,
+            cache,
+        };
+
+        if fs.is_valid(fs.root_cluster) {
+            Ok(fs)
+        } else {
+            Err(fs.cache.into_inner())
+        }
+    }
+
+    fn cluster_size(&self) -> u32 {
+        self.sectors_per_cluster * BLOCK_SIZE as u32
+    }
+
+    fn is_valid(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster < self.cluster_count + 2
+    }
+
+    fn cluster_sector(&self, cluster: u32) -> u32 {
+        self.data_start + (cluster - 2) * self.sectors_per_cluster
+    }
+
+    fn fat_entry(&mut self, cluster: u32) -> Result<u32, ()> {
+        let offset = cluster * 4;
+        let sector = self.fat_start + offset / BLOCK_SIZE as u32;
+        let block = Block::read(&mut self.cache, sector)?;
+
+        Ok(get_u32(block.data(), offset as usize % BLOCK_SIZE) & CLUSTER_MASK)
+    }
+
+    // every copy of the FAT is kept identical
+    fn set_fat_entry(&mut self, cluster: u32, value: u32) -> Result<(), ()> {
+        let offset = cluster * 4;
+        let i = offset as usize % BLOCK_SIZE;
+
+        for n in 0..self.fat_count {
+            let sector = self.fat_start + n * self.fat_size + offset / BLOCK_SIZE as u32;
+            let mut block = Block::read(&mut self.cache, sector)?;
+
+            // the high 4 bits are reserved and must be preserved
+            let old = get_u32(block.data(), i);
+            set_u32(
+                block.data_mut(),
+                i,
+                old & !CLUSTER_MASK | value & CLUSTER_MASK,
+            );
+            block.write(&mut self.cache)?;
+        }
+
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, ()> {
+        match self.fat_entry(cluster)? {
+            next if next >= END_OF_CHAIN => Ok(None),
+            BAD_CLUSTER => Err(()),
+            next if self.is_valid(next) => Ok(Some(next)),
+            _ => Err(()),
+        }
+    }
+
+    /// Takes a free cluster, clears it and links it after `prev` when given.
+    fn alloc_cluster(&mut self, prev: Option<u32>) -> Result<u32, ()> {
+        let count = self.cluster_count;
+        let start = self.next_free;
+
+        let mut found = None;
+
+        for i in 0..count {
+            let cluster = 2 + (start - 2 + i) % count;
+
+            if self.fat_entry(cluster)? == 0 {
+                found = Some(cluster);
+                break;
+            }
+        }
+
+        let cluster = found.ok_or(())?;
+
+        self.set_fat_entry(cluster, CLUSTER_MASK)?;
+        self.next_free = cluster;
+
+        let sector = self.cluster_sector(cluster);
+
+        for n in 0..self.sectors_per_cluster {
+            Block::new(sector + n).write(&mut self.cache)?;
+        }
+
+        if let Some(prev) = prev {
+            self.set_fat_entry(prev, cluster)?;
+        }
+
+        Ok(cluster)
+    }
+
+    fn free_chain(&mut self, cluster: u32) -> Result<(), ()> {
+        let mut cluster = Some(cluster).filter(|&c| self.is_valid(c));
+
+        while let Some(current) = cluster {
+            cluster = self.next_cluster(current)?;
+            self.set_fat_entry(current, 0)?;
+        }
+
+        Ok(())
+    }
+
+    // the `index`th cluster of the chain starting at `first`, growing the chain if asked
+    fn cluster_at(&mut self, first: u32, index: u32, alloc: bool) -> Result<u32, ()> {
+        let mut cluster = first;
+
+        for _ in 0..index {
+            cluster = match self.next_cluster(cluster)? {
+                Some(next) => next,
+                None if alloc => self.alloc_cluster(Some(cluster))?,
+                None => return Err(()),
+            };
+        }
+
+        Ok(cluster)
+    }
+
+    // the sector holding the byte at `offset` of the chain starting at `first`
+    fn sector_at(&mut self, first: u32, offset: u32, alloc: bool) -> Result<u32, ()> {
+        let cluster_size = self.cluster_size();
+        let cluster = self.cluster_at(first, offset / cluster_size, alloc)?;
+
+        Ok(self.cluster_sector(cluster) + offset % cluster_size / BLOCK_SIZE as u32)
+    }
+
+    /// Every entry of the directory starting at `cluster`, followed by the offset where new
+    /// entries can be appended.
+    fn records(&mut self, cluster: u32) -> Result<(Vec<Record>, u32), ()> {
+        let mut records = Vec::new();
+        let mut lfn: Vec<u16> = Vec::new();
+        let mut lfn_checksum = 0;
+        let mut lfn_start = 0;
+
+        let mut cluster = Some(cluster);
+        let mut pos = 0;
+
+        while let Some(current) = cluster {
+            let sector = self.cluster_sector(current);
+
+            for n in 0..self.sectors_per_cluster {
+                let block = Block::read(&mut self.cache, sector + n)?;
+
+                for raw in block.data().chunks(RECORD_SIZE) {
+                    let offset = pos;
+                    pos += RECORD_SIZE as u32;
+
+                    match raw[0] {
+                        RECORD_END => return Ok((records, offset)),
+                        RECORD_DELETED => {
+                            lfn.clear();
+                            continue;
+                        }
+                        _ => {}
+                    }
+
+                    if raw[11] == ATTR_LFN {
+                        let seq = raw[0] & 0x1F;
+
+                        if raw[0] & LFN_LAST != 0 {
+                            lfn = vec![0xFFFF; seq as usize * LFN_CHARS];
+                            lfn_checksum = raw[13];
+                            lfn_start = offset;
+                        }
+
+                        let base = (seq as usize).saturating_sub(1) * LFN_CHARS;
+
+                        if base + LFN_CHARS <= lfn.len() {
+                            for (i, offset) in LFN_OFFSETS.iter().enumerate() {
+                                lfn[base + i] = get_u16(raw, *offset);
+                            }
+                        }
+
+                        continue;
+                    }
+
+                    if raw[11] & ATTR_VOLUME_ID != 0 {
+                        lfn.clear();
+                        continue;
+                    }
+
+                    let (name, start) = if !lfn.is_empty() && checksum(&raw[0..11]) == lfn_checksum
+                    {
+                        let chars = lfn.iter().copied().take_while(|&c| c != 0 && c != 0xFFFF);
+                        let name = char::decode_utf16(chars)
+                            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                            .collect();
+
+                        (name, lfn_start)
+                    } else {
+                        (decode_short_name(raw), offset)
+                    };
+
+                    lfn.clear();
+
+                    let mut record = Record {
+                        name,
+                        start,
+                        pos: offset,
+                        raw: [0; RECORD_SIZE],
+                    };
+
+                    record.raw.copy_from_slice(raw);
+                    records.push(record);
+                }
+            }
+
+            cluster = self.next_cluster(current)?;
+        }
+
+        Ok((records, pos))
+    }
+
+    fn write_record(&mut self, dir: u32, pos: u32, raw: &[u8]) -> Result<(), ()> {
+        let sector = self.sector_at(dir, pos, true)?;
+        let i = pos as usize % BLOCK_SIZE;
+
+        let mut block = Block::read(&mut self.cache, sector)?;
+        block.data_mut()[i..i + RECORD_SIZE].copy_from_slice(raw);
+        block.write(&mut self.cache)
+    }
+
+    /// Rewrites the first cluster and the size stored in the entry of `name`.
+    fn update_entry(&mut self, dir: u32, name: &str, cluster: u32, size: u32) -> Result<(), ()> {
+        let (records, _) = self.records(dir)?;
+        let mut record = records.into_iter().find(|r| r.name == name).ok_or(())?;
+
+        set_u16(&mut record.raw, 20, (cluster >> 16) as u16);
+        set_u16(&mut record.raw, 26, cluster as u16);
+        set_u32(&mut record.raw, 28, size);
+
+        self.write_record(dir, record.pos, &record.raw)
+    }
+
+    /// The first cluster and the size of `file` as stored in its entry, which a handle doesn't
+    /// see change when another one writes to the file or truncates it.
+    fn stored(&mut self, file: &File) -> Result<(u32, u32), ()> {
+        let (records, _) = self.records(file.contained_by.addr)?;
+        let record = records.iter().find(|r| r.name == file.name).ok_or(())?;
+        Ok((record.cluster(), record.size()))
+    }
+
+    /// Checks that `name` is free in the directory starting at `dir` and finds how to store it.
+    /// The entry called `renamed` doesn't count, so that it can change the case of its name.
+    fn new_name(&mut self, dir: u32, name: &str, renamed: Option<&str>) -> Result<NewName, ()> {
+        if name.encode_utf16().count() > MAX_NAME_LEN || name == "." || name == ".." {
+            return Err(());
+        }
+
+        let (records, end) = self.records(dir)?;
+
+        // names are case insensitive
+        if records
+            .iter()
+            .any(|r| r.name.eq_ignore_ascii_case(name) && Some(r.name.as_str()) != renamed)
+        {
+            return Err(());
+        }
+
+        match exact_short_name(name) {
+            Some(short_name) => Ok(NewName {
+                short_name,
+                lfn: Vec::new(),
+                pos: end,
+            }),
+            None => {
+                let short_name = (1..1_000_000)
+                    .map(|n| alias(name, n))
+                    .find(|alias| records.iter().all(|r| r.raw[0..11] != alias[..]))
+                    .ok_or(())?;
+
+                Ok(NewName {
+                    short_name,
+                    lfn: Self::lfn_records(name, &short_name),
+                    pos: end,
+                })
+            }
+        }
+    }
+
+    // writes the long name records of `name` followed by the short name record `short`
+    fn write_records(
+        &mut self,
+        dir: u32,
+        name: &NewName,
+        short: &[u8; RECORD_SIZE],
+    ) -> Result<(), ()> {
+        let mut pos = name.pos;
+
+        for raw in name.lfn.iter() {
+            self.write_record(dir, pos, raw)?;
+            pos += RECORD_SIZE as u32;
+        }
+
+        self.write_record(dir, pos, short)
+    }
+
+    // marks the short name record of `record` and its long name records as deleted
+    fn delete_records(&mut self, dir: u32, record: &Record) -> Result<(), ()> {
+        for pos in (record.start..=record.pos).step_by(RECORD_SIZE) {
+            let sector = self.sector_at(dir, pos, false)?;
+            let mut block = Block::read(&mut self.cache, sector)?;
+
+            block.data_mut()[pos as usize % BLOCK_SIZE] = RECORD_DELETED;
+            block.write(&mut self.cache)?;
+        }
+
+        Ok(())
+    }
+
+    // the root directory is referred to as cluster 0 by the `..` records
+    fn parent_ref(&self, dir: u32) -> u32 {
+        match dir {
+            addr if addr == self.root_cluster => 0,
+            addr => addr,
+        }
+    }
+
+    fn dotdot_record(parent: u32) -> [u8; RECORD_SIZE] {
+        Self::short_record(b"..         ", ATTR_DIRECTORY, parent)
+    }
+
+    fn short_record(short_name: &[u8; 11], attr: u8, cluster: u32) -> [u8; RECORD_SIZE] {
+        let mut raw = [0; RECORD_SIZE];
+
+        raw[0..11].copy_from_slice(short_name);
+        raw[11] = attr;
+        set_u16(&mut raw, 16, DEFAULT_DATE); // creation date
+        set_u16(&mut raw, 18, DEFAULT_DATE); // last access date
+        set_u16(&mut raw, 20, (cluster >> 16) as u16);
+        set_u16(&mut raw, 24, DEFAULT_DATE); // last write date
+        set_u16(&mut raw, 26, cluster as u16);
+        raw
+    }
+
+    fn lfn_records(name: &str, short_name: &[u8; 11]) -> Vec<[u8; RECORD_SIZE]> {
+        let chars = name.encode_utf16().collect::<Vec<_>>();
+        let count = chars.chunks(LFN_CHARS).len();
+        let sum = checksum(short_name);
+
+        // stored last chunk first
+        (1..=count)
+            .rev()
+            .map(|seq| {
+                let mut raw = [0; RECORD_SIZE];
+
+                raw[0] = seq as u8 | if seq == count { LFN_LAST } else { 0 };
+                raw[11] = ATTR_LFN;
+                raw[13] = sum;
+
+                for (i, offset) in LFN_OFFSETS.iter().enumerate() {
+                    // the name is terminated by a null character and padded with 0xFFFF
+                    let c = match ((seq - 1) * LFN_CHARS + i).cmp(&chars.len()) {
+                        core::cmp::Ordering::Less => chars[(seq - 1) * LFN_CHARS + i],
+                        core::cmp::Ordering::Equal => 0,
+                        core::cmp::Ordering::Greater => 0xFFFF,
+                    };
+
+                    set_u16(&mut raw, *offset, c);
+                }
+
+                raw
+            })
+            .collect()
+    }
+}
+
+impl FileSystem for FatFs {
+    fn root(&self) -> u32 {
+        self.root_cluster
+    }
+
+    fn entries(&mut self, dir: &Directory) -> Result<Vec<DirEntry>, ()> {
+        let (records, _) = self.records(dir.addr)?;
+
+        let entries = records
+            .into_iter()
+            .filter(|r| r.name != "." && r.name != "..")
+            .map(|r| DirEntry {
+                dir: dir.clone(),
+                addr: r.cluster(),
+                size: r.size(),
+                kind: if r.is_dir() {
+                    FileKind::Dir
+                } else {
+                    FileKind::File
+                },
+                name: r.name,
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn read(&mut self, file: &File, buf: &mut [u8]) -> Result<usize, ()> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let (first, size) = self.stored(file)?;
+
+        if file.offset >= size || !self.is_valid(first) {
+            return Ok(0);
+        }
+
+        let len = buf.len().min((size - file.offset) as usize);
+        let mut offset = file.offset;
+        let mut n = 0;
+
+        while n < len {
+            let sector = self.sector_at(first, offset, false)?;
+            let i = offset as usize % BLOCK_SIZE;
+            let count = (BLOCK_SIZE - i).min(len - n);
+
+            let block = Block::read(&mut self.cache, sector)?;
+            buf[n..n + count].copy_from_slice(&block.data()[i..i + count]);
+
+            n += count;
+            offset += count as u32;
+        }
+
+        Ok(n)
+    }
+
+    fn write(&mut self, file: &mut File, buf: &[u8]) -> Result<usize, ()> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        (file.addr, file.size) = self.stored(file)?;
+
+        // empty files have no cluster
+        if !self.is_valid(file.addr) {
+            file.addr = self.alloc_cluster(None)?;
+        }
+
+        let mut offset = file.offset;
+        let mut n = 0;
+
+        while n < buf.len() {
+            let sector = self.sector_at(file.addr, offset, true)?;
+            let i = offset as usize % BLOCK_SIZE;
+            let count = (BLOCK_SIZE - i).min(buf.len() - n);
+
+            let mut block = Block::read(&mut self.cache, sector)?;
+            block.data_mut()[i..i + count].copy_from_slice(&buf[n..n + count]);
+            block.write(&mut self.cache)?;
+
+            n += count;
+            offset += count as u32;
+        }
+
+        file.size = file.size.max(offset);
+        self.update_entry(file.contained_by.addr, &file.name, file.addr, file.size)?;
+
+        Ok(n)
+    }
+
+    fn create(&mut self, dir: &mut Directory, name: &str, kind: FileKind) -> Result<DirEntry, ()> {
+        let new_name = self.new_name(dir.addr, name, None)?;
+
+        let (attr, cluster) = match kind {
+            FileKind::Dir => {
+                let cluster = self.alloc_cluster(None)?;
+
+                let dot = Self::short_record(b".          ", ATTR_DIRECTORY, cluster);
+                let dotdot = Self::dotdot_record(self.parent_ref(dir.addr));
+
+                self.write_record(cluster, 0, &dot)?;
+                self.write_record(cluster, RECORD_SIZE as u32, &dotdot)?;
+
+                (ATTR_DIRECTORY, cluster)
+            }
+            FileKind::File => (ATTR_ARCHIVE, 0),
+            FileKind::Device | FileKind::Link | FileKind::Fifo => return Err(()),
+        };
+
+        let raw = Self::short_record(&new_name.short_name, attr, cluster);
+        self.write_records(dir.addr, &new_name, &raw)?;
+
+        Ok(DirEntry {
+            dir: dir.clone(),
+            name: name.into(),
+            addr: cluster,
+            size: 0,
+            kind,
+        })
+    }
+
+    fn remove(&mut self, entry: &DirEntry) -> Result<(), ()> {
+        let (records, _) = self.records(entry.dir.addr)?;
+        let record = records.iter().find(|r| r.name == entry.name).ok_or(())?;
+
+        self.delete_records(entry.dir.addr, record)?;
+        self.free_chain(record.cluster())
+    }
+
+    fn rename(&mut self, entry: &DirEntry, dir: &mut Directory, name: &str) -> Result<(), ()> {
+        let (records, _) = self.records(entry.dir.addr)?;
+        let record = records
+            .into_iter()
+            .find(|r| r.name == entry.name)
+            .ok_or(())?;
+        let renamed = (dir.addr == entry.dir.addr).then_some(entry.name.as_str());
+        let new_name = self.new_name(dir.addr, name, renamed)?;
+
+        // the new records keep the attributes, the dates and the content of the old ones
+        let mut raw = record.raw;
+        raw[0..11].copy_from_slice(&new_name.short_name);
+
+        self.write_records(dir.addr, &new_name, &raw)?;
+        self.delete_records(entry.dir.addr, &record)?;
+
+        if record.is_dir() && dir.addr != entry.dir.addr {
+            let dotdot = Self::dotdot_record(self.parent_ref(dir.addr));
+            self.write_record(record.cluster(), RECORD_SIZE as u32, &dotdot)?;
+        }
+
+        Ok(())
+    }
+
+    fn truncate(&mut self, file: &mut File) -> Result<(), ()> {
+        (file.addr, _) = self.stored(file)?;
+        self.free_chain(file.addr)?;
+
+        file.addr = 0;
+        file.size = 0;
+        self.update_entry(file.contained_by.addr, &file.name, file.addr, file.size)
+    }
+
+    // FAT has no owners, the root directory has no record
+    fn metadata(&mut self, entry: &DirEntry, meta: &mut Metadata) -> Result<(), ()> {
+        if entry.name.is_empty() {
+            return Ok(());
+        }
+
+        let (records, _) = self.records(entry.dir.addr)?;
+        let record = records.iter().find(|r| r.name == entry.name).ok_or(())?;
+        let raw = &record.raw;
+
+        if record.is_read_only() {
+            meta.permissions = ProcessPrivileges::from_mode(0o555, entry.kind).bits();
+        }
+
+        meta.created = timestamp(get_u16(raw, 16), get_u16(raw, 14));
+        meta.accessed = timestamp(get_u16(raw, 18), 0);
+        meta.modified = timestamp(get_u16(raw, 24), get_u16(raw, 22));
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<(), ()> {
+        if self.dirty && self.fs_info != 0 {
+            let mut block = Block::read(&mut self.cache, self.fs_info)?;
+            let data = block.data_mut();
+
+            // the free count is only a hint, marking it unknown is allowed
+            if get_u32(data, 0) == FS_INFO_LEAD_SIGNATURE
+                && get_u32(data, 484) == FS_INFO_STRUCT_SIGNATURE
+            {
+                set_u32(data, 488, 0xFFFF_FFFF);
+                set_u32(data, 492, self.next_free);
+                block.write(&mut self.cache)?;
+            }
+
+            self.dirty = false;
+        }
+
+        self.cache.sync()
+    }
+}
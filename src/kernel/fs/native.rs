@@ -0,0 +1,597 @@
+pub(crate) mod bitmap;
+pub(crate) mod superblock;
+
+use self::superblock::{Superblock, BITMAP_ADDR};
+use super::{
+    block::{Block, BlockDevice, BLOCK_SIZE},
+    cache::{BlockCache, CACHE_CAPACITY},
+    DirEntry, Directory, File, FileKind, FileSystem, Metadata,
+};
+use crate::kernel::process::ProcessPrivileges;
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+/// The blocks holding the data of a file or a directory are chained together, the first
+/// bytes of each of them are the address of the next block (0 for the last one).
+const DATA_OFFSET: usize = 4;
+const DATA_SIZE: usize = BLOCK_SIZE - DATA_OFFSET;
+
+// entries are stored as kind (1 byte), addr (4 bytes), size (4 bytes), user (4 bytes), group
+// (4 bytes), permissions (8 bytes), link count (2 bytes), name length (1 byte) followed by the
+// name, the first entry with an address of 0 ends the block
+const ENTRY_HEADER_SIZE: usize = 28;
+const MAX_NAME_LEN: usize = u8::MAX as usize;
+
+// integers of the native filesystem are stored big endian
+
+fn get_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn set_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+pub(crate) fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_be_bytes(bytes)
+}
+
+pub(crate) fn set_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+fn get_u64(buf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_be_bytes(bytes)
+}
+
+fn set_u64(buf: &mut [u8], offset: usize, value: u64) {
+    buf[offset..offset + 8].copy_from_slice(&value.to_be_bytes());
+}
+
+// length of the entry starting at `pos`, if there is one
+fn entry_len(data: &[u8], pos: usize) -> Option<usize> {
+    if pos + ENTRY_HEADER_SIZE > data.len() || get_u32(data, pos + 1) == 0 {
+        return None;
+    }
+
+    let len = ENTRY_HEADER_SIZE + data[pos + ENTRY_HEADER_SIZE - 1] as usize;
+    (pos + len <= data.len()).then_some(len)
+}
+
+fn decode_entry(dir: &Directory, data: &[u8]) -> Option<DirEntry> {
+    Some(DirEntry {
+        dir: dir.clone(),
+        name: String::from_utf8_lossy(&data[ENTRY_HEADER_SIZE..]).into(),
+        addr: get_u32(data, 1),
+        size: get_u32(data, 5),
+        kind: FileKind::try_from(data[0]).ok()?,
+    })
+}
+
+fn encode_entry(entry: &DirEntry, attrs: &Attributes, buf: &mut [u8]) {
+    buf[0] = entry.kind as u8;
+    set_u32(buf, 1, entry.addr);
+    set_u32(buf, 5, entry.size);
+    attrs.encode(buf);
+    buf[ENTRY_HEADER_SIZE - 1] = entry.name.len() as u8;
+    buf[ENTRY_HEADER_SIZE..ENTRY_HEADER_SIZE + entry.name.len()]
+        .copy_from_slice(entry.name.as_bytes());
+}
+
+/// The owner, the permissions and the link count stored in an entry, they are kept out of
+/// `DirEntry` since most filesystems don't have them.
+///
+/// A file with hard links has an entry per name, all of them hold the same attributes.
+#[derive(Debug, Clone, Copy)]
+struct Attributes {
+    user: u32,
+    group: u32,
+    permissions: u64,
+    links: u16,
+}
+
+impl Attributes {
+    // what a node gets until `set_owner` is called, like on filesystems without owners
+    fn new(kind: FileKind) -> Self {
+        Self {
+            user: 0,
+            group: 0,
+            permissions: ProcessPrivileges::from_mode(0o777, kind).bits(),
+            links: 1,
+        }
+    }
+
+    fn decode(entry: &[u8]) -> Self {
+        Self {
+            user: get_u32(entry, 9),
+            group: get_u32(entry, 13),
+            permissions: get_u64(entry, 17),
+            links: get_u16(entry, 25),
+        }
+    }
+
+    fn encode(&self, entry: &mut [u8]) {
+        set_u32(entry, 9, self.user);
+        set_u32(entry, 13, self.group);
+        set_u64(entry, 17, self.permissions);
+        set_u16(entry, 25, self.links);
+    }
+}
+
+/// The filesystem of Prestige, see `Superblock` for its layout.
+pub(crate) struct NativeFs {
+    cache: BlockCache,
+    sb: Superblock,
+}
+
+impl NativeFs {
+    /// Mounts the filesystem stored on `dev`, giving the device back if there is none.
+    pub(crate) fn open(dev: Box<dyn BlockDevice>) -> Result<Self, Box<dyn BlockDevice>> {
+        let mut cache = BlockCache::new(dev, CACHE_CAPACITY);
+
+        match Superblock::read(&mut cache) {
+            Some(sb) if sb.block_count() <= cache.block_count() => Ok(Self { cache, sb }),
+            _ => Err(cache.into_inner()),
+        }
+    }
+
+    /// Writes an empty filesystem on `dev`.
+    pub(crate) fn format(dev: Box<dyn BlockDevice>) -> Option<Self> {
+        let mut cache = BlockCache::new(dev, CACHE_CAPACITY);
+        let sb = Superblock::new(cache.block_count())?;
+
+        for addr in BITMAP_ADDR..sb.data_addr() {
+            Block::new(addr).write(&mut cache).ok()?;
+        }
+
+        sb.write(&mut cache).ok()?;
+
+        let mut fs = Self { cache, sb };
+
+        // the root directory is always the first data block
+        let root = fs.alloc_block().ok()?;
+
+        if root.addr() != fs.sb.data_addr() {
+            return None;
+        }
+
+        fs.cache.sync().ok()?;
+        Some(fs)
+    }
+
+    fn read_block(&mut self, addr: u32) -> Result<Block, ()> {
+        Block::read(&mut self.cache, addr)
+    }
+
+    fn write_block(&mut self, block: &Block) -> Result<(), ()> {
+        block.write(&mut self.cache)
+    }
+
+    /// Takes a free block from the allocation bitmap and clears it.
+    fn alloc_block(&mut self) -> Result<Block, ()> {
+        let block = Block::new(bitmap::alloc(&mut self.cache, &mut self.sb).ok_or(())?);
+        self.write_block(&block)?;
+        Ok(block)
+    }
+
+    /// Gives back `addr` and every block chained after it to the allocation bitmap.
+    fn free_chain(&mut self, addr: u32) {
+        let mut addr = addr;
+
+        while addr != 0 {
+            let next = self
+                .read_block(addr)
+                .map(|block| get_u32(block.data(), 0))
+                .unwrap_or(0);
+
+            bitmap::free(&mut self.cache, &mut self.sb, addr);
+            addr = next;
+        }
+    }
+
+    fn next_block(&mut self, block: &Block) -> Option<Block> {
+        match get_u32(block.data(), 0) {
+            0 => None,
+            addr => self.read_block(addr).ok(),
+        }
+    }
+
+    /// Returns the next block of the chain, linking a newly allocated one if this is the last.
+    ///
+    /// The caller is responsible for writing `block` back when a link has been added.
+    fn next_or_alloc(&mut self, block: &mut Block) -> Result<Block, ()> {
+        if let Some(next) = self.next_block(block) {
+            return Ok(next);
+        }
+
+        let next = self.alloc_block()?;
+        set_u32(block.data_mut(), 0, next.addr());
+        Ok(next)
+    }
+
+    // the block holding the byte at `offset` of the chain starting at `addr` and the offset of
+    // its first byte
+    fn block_at(&mut self, addr: u32, offset: u32, alloc: bool) -> Result<(Block, u32), ()> {
+        let mut block = self.read_block(addr)?;
+        let mut start = 0;
+
+        while offset >= start + DATA_SIZE as u32 {
+            block = if alloc {
+                let next = self.next_or_alloc(&mut block)?;
+                self.write_block(&block)?;
+                next
+            } else {
+                self.next_block(&block).ok_or(())?
+            };
+
+            start += DATA_SIZE as u32;
+        }
+
+        Ok((block, start))
+    }
+
+    // the block holding the entry of `name` in the directory starting at `dir`, along with the
+    // position and the length of the entry in the data of the block
+    fn find_entry(&mut self, dir: u32, name: &str) -> Result<(Block, usize, usize), ()> {
+        let mut block = self.read_block(dir)?;
+
+        loop {
+            let data = &block.data()[DATA_OFFSET..];
+            let mut pos = 0;
+
+            while let Some(n) = entry_len(data, pos) {
+                if &data[pos + ENTRY_HEADER_SIZE..pos + n] == name.as_bytes() {
+                    return Ok((block, pos, n));
+                }
+
+                pos += n;
+            }
+
+            block = self.next_block(&block).ok_or(())?;
+        }
+    }
+
+    /// Applies `f` to the entry of `name` in the directory starting at `dir`.
+    ///
+    /// The other entries of a node with hard links get the same treatment so they stay in sync.
+    fn update_entry(&mut self, dir: u32, name: &str, f: impl Fn(&mut [u8])) -> Result<(), ()> {
+        let (mut block, pos, n) = self.find_entry(dir, name)?;
+        let entry = &mut block.data_mut()[DATA_OFFSET + pos..DATA_OFFSET + pos + n];
+
+        if Attributes::decode(entry).links > 1 {
+            let addr = get_u32(entry, 1);
+            return self.update_links(self.sb.data_addr(), addr, &f);
+        }
+
+        f(entry);
+        self.write_block(&block)
+    }
+
+    // applies `f` to every entry of the node at `addr` found below the directory starting at
+    // `dir`, the whole tree is searched since entries don't know about each other
+    fn update_links(&mut self, dir: u32, addr: u32, f: &dyn Fn(&mut [u8])) -> Result<(), ()> {
+        let mut block = self.read_block(dir)?;
+        let mut subdirs = Vec::new();
+
+        loop {
+            let mut pos = 0;
+            let mut changed = false;
+
+            while let Some(n) = entry_len(&block.data()[DATA_OFFSET..], pos) {
+                let entry = &mut block.data_mut()[DATA_OFFSET + pos..DATA_OFFSET + pos + n];
+
+                if get_u32(entry, 1) == addr {
+                    f(entry);
+                    changed = true;
+                } else if entry[0] == FileKind::Dir as u8 {
+                    subdirs.push(get_u32(entry, 1));
+                }
+
+                pos += n;
+            }
+
+            if changed {
+                self.write_block(&block)?;
+            }
+
+            match self.next_block(&block) {
+                Some(next) => block = next,
+                None => break,
+            }
+        }
+
+        for subdir in subdirs {
+            self.update_links(subdir, addr, f)?;
+        }
+
+        Ok(())
+    }
+
+    fn attributes(&mut self, entry: &DirEntry) -> Result<Attributes, ()> {
+        let (block, pos, _) = self.find_entry(entry.dir.addr, &entry.name)?;
+        Ok(Attributes::decode(&block.data()[DATA_OFFSET + pos..]))
+    }
+
+    /// Writes `entry` after the last entry of `dir` and grows its size accordingly.
+    fn append_entry(
+        &mut self,
+        dir: &mut Directory,
+        entry: &DirEntry,
+        attrs: &Attributes,
+    ) -> Result<(), ()> {
+        let len = ENTRY_HEADER_SIZE + entry.name.len();
+
+        // new entries are appended after the last one of the last block
+        let mut block = self.read_block(dir.addr)?;
+
+        while let Some(next) = self.next_block(&block) {
+            block = next;
+        }
+
+        let mut pos = 0;
+
+        while let Some(n) = entry_len(&block.data()[DATA_OFFSET..], pos) {
+            pos += n;
+        }
+
+        if pos + len > DATA_SIZE {
+            let next = self.next_or_alloc(&mut block)?;
+            self.write_block(&block)?;
+            block = next;
+            pos = 0;
+        }
+
+        encode_entry(
+            entry,
+            attrs,
+            &mut block.data_mut()[DATA_OFFSET + pos..DATA_OFFSET + pos + len],
+        );
+        self.write_block(&block)?;
+
+        self.resize_dir(dir, dir.size + len as u32)
+    }
+
+    /// Takes the entry of `name` out of `dir`, moving the following entries of its block back.
+    fn remove_entry(&mut self, dir: &mut Directory, name: &str) -> Result<(), ()> {
+        let (mut block, pos, n) = self.find_entry(dir.addr, name)?;
+        let data = &mut block.data_mut()[DATA_OFFSET..];
+
+        data.copy_within(pos + n.., pos);
+        data[DATA_SIZE - n..].fill(0);
+        self.write_block(&block)?;
+
+        self.resize_dir(dir, dir.size.saturating_sub(n as u32))
+    }
+
+    // the size of a directory is the length of its entries, it is stored in its parent
+    fn resize_dir(&mut self, dir: &mut Directory, size: u32) -> Result<(), ()> {
+        dir.size = size;
+
+        match &dir.parent {
+            Some(parent) => {
+                let size = dir.size;
+                self.update_entry(parent.addr, &dir.name, |entry| set_u32(entry, 5, size))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl FileSystem for NativeFs {
+    fn root(&self) -> u32 {
+        self.sb.data_addr()
+    }
+
+    fn entries(&mut self, dir: &Directory) -> Result<Vec<DirEntry>, ()> {
+        let mut entries = Vec::new();
+        let mut block = Some(self.read_block(dir.addr)?);
+
+        while let Some(current) = block {
+            let data = &current.data()[DATA_OFFSET..];
+            let mut pos = 0;
+
+            while let Some(n) = entry_len(data, pos) {
+                entries.extend(decode_entry(dir, &data[pos..pos + n]));
+                pos += n;
+            }
+
+            block = self.next_block(&current);
+        }
+
+        Ok(entries)
+    }
+
+    fn read(&mut self, file: &File, buf: &mut [u8]) -> Result<usize, ()> {
+        if buf.is_empty() || file.offset >= file.size {
+            return Ok(0);
+        }
+
+        let (mut block, mut start) = self.block_at(file.addr, file.offset, false)?;
+        let mut offset = file.offset;
+        let mut n = 0;
+
+        loop {
+            let i = (offset - start) as usize;
+            let len = (DATA_SIZE - i)
+                .min(buf.len() - n)
+                .min((file.size - offset) as usize);
+
+            buf[n..n + len].copy_from_slice(&block.data()[DATA_OFFSET + i..DATA_OFFSET + i + len]);
+            n += len;
+            offset += len as u32;
+
+            if n == buf.len() || offset == file.size {
+                return Ok(n);
+            }
+
+            block = self.next_block(&block).ok_or(())?;
+            start += DATA_SIZE as u32;
+        }
+    }
+
+    fn write(&mut self, file: &mut File, buf: &[u8]) -> Result<usize, ()> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let (mut block, mut start) = self.block_at(file.addr, file.offset, true)?;
+        let mut offset = file.offset;
+        let mut n = 0;
+
+        loop {
+            let i = (offset - start) as usize;
+            let len = (DATA_SIZE - i).min(buf.len() - n);
+
+            block.data_mut()[DATA_OFFSET + i..DATA_OFFSET + i + len]
+                .copy_from_slice(&buf[n..n + len]);
+            n += len;
+            offset += len as u32;
+
+            if n == buf.len() {
+                self.write_block(&block)?;
+                break;
+            }
+
+            let next = self.next_or_alloc(&mut block)?;
+            self.write_block(&block)?;
+            block = next;
+            start += DATA_SIZE as u32;
+        }
+
+        if offset > file.size {
+            file.size = offset;
+            let size = file.size;
+            self.update_entry(file.contained_by.addr, &file.name, |entry| {
+                set_u32(entry, 5, size)
+            })?;
+        }
+
+        Ok(n)
+    }
+
+    fn create(&mut self, dir: &mut Directory, name: &str, kind: FileKind) -> Result<DirEntry, ()> {
+        if name.len() > MAX_NAME_LEN {
+            return Err(());
+        }
+
+        let node = self.alloc_block()?;
+
+        let entry = DirEntry {
+            dir: dir.clone(),
+            name: name.into(),
+            addr: node.addr(),
+            size: 0,
+            kind,
+        };
+
+        self.append_entry(dir, &entry, &Attributes::new(kind))?;
+        Ok(entry)
+    }
+
+    fn remove(&mut self, entry: &DirEntry) -> Result<(), ()> {
+        let links = self.attributes(entry)?.links;
+        self.remove_entry(&mut entry.dir.clone(), &entry.name)?;
+
+        // the data stays as long as another entry links to it
+        if links > 1 {
+            let set_links = |entry: &mut [u8]| set_u16(entry, 25, links - 1);
+            return self.update_links(self.sb.data_addr(), entry.addr, &set_links);
+        }
+
+        self.free_chain(entry.addr);
+        Ok(())
+    }
+
+    fn link(&mut self, entry: &DirEntry, dir: &mut Directory, name: &str) -> Result<(), ()> {
+        let mut attrs = self.attributes(entry)?;
+
+        if name.len() > MAX_NAME_LEN || entry.is_dir() || attrs.links == u16::MAX {
+            return Err(());
+        }
+
+        attrs.links += 1;
+        let links = attrs.links;
+        self.update_entry(entry.dir.addr, &entry.name, |entry| {
+            set_u16(entry, 25, links)
+        })?;
+
+        let linked = DirEntry {
+            dir: dir.clone(),
+            name: name.into(),
+            ..entry.clone()
+        };
+
+        self.append_entry(dir, &linked, &attrs)
+    }
+
+    fn rename(&mut self, entry: &DirEntry, dir: &mut Directory, name: &str) -> Result<(), ()> {
+        if name.len() > MAX_NAME_LEN {
+            return Err(());
+        }
+
+        let attrs = self.attributes(entry)?;
+        let mut old_dir = entry.dir.clone();
+        self.remove_entry(&mut old_dir, &entry.name)?;
+
+        if old_dir.addr == dir.addr {
+            dir.size = old_dir.size;
+        }
+
+        let moved = DirEntry {
+            dir: dir.clone(),
+            name: name.into(),
+            ..entry.clone()
+        };
+
+        self.append_entry(dir, &moved, &attrs)
+    }
+
+    /// Frees every block of the file except the first one and sets its size to 0.
+    fn truncate(&mut self, file: &mut File) -> Result<(), ()> {
+        let first = self.read_block(file.addr)?;
+
+        self.free_chain(get_u32(first.data(), 0));
+        self.write_block(&Block::new(file.addr))?;
+
+        file.size = 0;
+        self.update_entry(file.contained_by.addr, &file.name, |entry| {
+            set_u32(entry, 5, 0)
+        })
+    }
+
+    fn metadata(&mut self, entry: &DirEntry, meta: &mut Metadata) -> Result<(), ()> {
+        // the root has no entry to store an owner in
+        if entry.name.is_empty() {
+            return Ok(());
+        }
+
+        let attrs = self.attributes(entry)?;
+
+        meta.user = attrs.user as u64;
+        meta.group = attrs.group as u64;
+        meta.permissions = attrs.permissions;
+        meta.links = attrs.links as u64;
+        Ok(())
+    }
+
+    fn set_owner(
+        &mut self,
+        entry: &DirEntry,
+        user: u64,
+        group: u64,
+        permissions: ProcessPrivileges,
+    ) -> Result<(), ()> {
+        self.update_entry(entry.dir.addr, &entry.name, |entry| {
+            set_u32(entry, 9, user as u32);
+            set_u32(entry, 13, group as u32);
+            set_u64(entry, 17, permissions.bits());
+        })
+    }
+
+    fn sync(&mut self) -> Result<(), ()> {
+        self.cache.sync()
+    }
+}
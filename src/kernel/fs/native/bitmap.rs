@@ -0,0 +1,76 @@
+use super::superblock::{Superblock, BITMAP_ADDR, BITS_PER_BLOCK};
+use crate::kernel::fs::block::{Block, BlockDevice};
+use bit_field::BitField;
+
+// position of the bit tracking the data block `addr`: (bitmap block, byte, bit)
+fn locate(sb: &Superblock, addr: u32) -> Option<(u32, usize, usize)> {
+    let data_addr = sb.data_addr();
+
+    if addr < data_addr || addr >= sb.block_count() {
+        return None;
+    }
+
+    let i = addr - data_addr;
+    let bit = (i % BITS_PER_BLOCK) as usize;
+
+    Some((BITMAP_ADDR + i / BITS_PER_BLOCK, bit / 8, bit % 8))
+}
+
+pub(crate) fn is_allocated(dev: &mut dyn BlockDevice, sb: &Superblock, addr: u32) -> bool {
+    let Some((bitmap_addr, byte, bit)) = locate(sb, addr) else {
+        return false;
+    };
+
+    Block::read(dev, bitmap_addr)
+        .map(|block| block.data()[byte].get_bit(bit))
+        .unwrap_or(false)
+}
+
+/// Marks the first free data block as used and returns its address.
+pub(crate) fn alloc(dev: &mut dyn BlockDevice, sb: &mut Superblock) -> Option<u32> {
+    let data_count = sb.data_count();
+
+    for n in 0..sb.bitmap_count() {
+        let mut block = Block::read(dev, BITMAP_ADDR + n).ok()?;
+
+        let Some(bit) = (0..BITS_PER_BLOCK as usize).find(|i| !block.data()[i / 8].get_bit(i % 8))
+        else {
+            continue;
+        };
+
+        let i = n * BITS_PER_BLOCK + bit as u32;
+
+        if i >= data_count {
+            return None;
+        }
+
+        block.data_mut()[bit / 8].set_bit(bit % 8, true);
+        block.write(dev).ok()?;
+
+        sb.set_alloc_count(sb.alloc_count() + 1);
+        sb.write(dev).ok()?;
+
+        return Some(sb.data_addr() + i);
+    }
+
+    None
+}
+
+pub(crate) fn free(dev: &mut dyn BlockDevice, sb: &mut Superblock, addr: u32) {
+    let Some((bitmap_addr, byte, bit)) = locate(sb, addr) else {
+        return;
+    };
+
+    let Ok(mut block) = Block::read(dev, bitmap_addr) else {
+        return;
+    };
+
+    if block.data()[byte].get_bit(bit) {
+        block.data_mut()[byte].set_bit(bit, false);
+
+        if block.write(dev).is_ok() {
+            sb.set_alloc_count(sb.alloc_count().saturating_sub(1));
+            sb.write(dev).ok();
+        }
+    }
+}
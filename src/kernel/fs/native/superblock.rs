@@ -0,0 +1,91 @@
+use super::{get_u32, set_u32};
+use crate::kernel::fs::block::{Block, BlockDevice, BLOCK_SIZE};
+
+const SIGNATURE: &[u8; 8] = b"PRESTIGE";
+const VERSION: u8 = 3;
+
+pub(crate) const SUPERBLOCK_ADDR: u32 = 0;
+pub(crate) const BITMAP_ADDR: u32 = SUPERBLOCK_ADDR + 1;
+
+// each bitmap block tracks one bit per data block
+pub(crate) const BITS_PER_BLOCK: u32 = (BLOCK_SIZE * 8) as u32;
+
+/// First block of the native filesystem.
+///
+/// The device is laid out as the superblock, followed by the allocation bitmap and then the
+/// data area whose first block is the root directory.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Superblock {
+    block_size: u32,
+    block_count: u32,
+    alloc_count: u32,
+}
+
+impl Superblock {
+    pub(crate) fn new(block_count: u32) -> Option<Self> {
+        let sb = Self {
+            block_size: BLOCK_SIZE as u32,
+            block_count,
+            alloc_count: 0,
+        };
+
+        // there must at least be room for the root directory
+        (block_count > BITMAP_ADDR && sb.data_count() > 0).then_some(sb)
+    }
+
+    pub(crate) fn read(dev: &mut dyn BlockDevice) -> Option<Self> {
+        let block = Block::read(dev, SUPERBLOCK_ADDR).ok()?;
+        let data = block.data();
+
+        if &data[0..8] != SIGNATURE || data[8] != VERSION {
+            return None;
+        }
+
+        let sb = Self {
+            block_size: get_u32(data, 9),
+            block_count: get_u32(data, 13),
+            alloc_count: get_u32(data, 17),
+        };
+
+        (sb.block_size == BLOCK_SIZE as u32).then_some(sb)
+    }
+
+    pub(crate) fn write(&self, dev: &mut dyn BlockDevice) -> Result<(), ()> {
+        let mut block = Block::new(SUPERBLOCK_ADDR);
+        let data = block.data_mut();
+
+        data[0..8].copy_from_slice(SIGNATURE);
+        data[8] = VERSION;
+        set_u32(data, 9, self.block_size);
+        set_u32(data, 13, self.block_count);
+        set_u32(data, 17, self.alloc_count);
+
+        block.write(dev)
+    }
+
+    pub(crate) const fn block_count(&self) -> u32 {
+        self.block_count
+    }
+
+    pub(crate) const fn alloc_count(&self) -> u32 {
+        self.alloc_count
+    }
+
+    pub(crate) fn set_alloc_count(&mut self, count: u32) {
+        self.alloc_count = count;
+    }
+
+    pub(crate) const fn bitmap_count(&self) -> u32 {
+        // every bitmap block covers itself plus `BITS_PER_BLOCK` data blocks
+        let n = self.block_count.saturating_sub(BITMAP_ADDR);
+        (n + BITS_PER_BLOCK) / (BITS_PER_BLOCK + 1)
+    }
+
+    pub(crate) const fn data_addr(&self) -> u32 {
+        BITMAP_ADDR + self.bitmap_count()
+    }
+
+    pub(crate) const fn data_count(&self) -> u32 {
+        self.block_count.saturating_sub(self.data_addr())
+    }
+}
@@ -0,0 +1,174 @@
+use super::block::{Block, BlockDevice};
+use alloc::vec::Vec;
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const MBR_TABLE_OFFSET: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+
+const TYPE_EMPTY: u8 = 0x00;
+const TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+const TYPES_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+
+// logical partitions form a linked list, stop following it after this many of them
+const MAX_LOGICAL: usize = 128;
+
+const GPT_HEADER_ADDR: u32 = 1;
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// A range of blocks of a disk described by its partition table.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PartitionEntry {
+    /// Number of the partition as other systems count them: 1 to 4 for the primary MBR
+    /// partitions, 5 and up for the logical ones, the index in the table plus 1 for GPT.
+    pub(crate) number: u32,
+    pub(crate) start: u32,
+    pub(crate) count: u32,
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn get_u64(buf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Reads the partition table of `dev`, GPT is used when the MBR says so.
+///
+/// An empty list is returned when there is no valid table, checksums are not verified.
+pub(crate) fn scan(dev: &mut dyn BlockDevice) -> Vec<PartitionEntry> {
+    let block_count = dev.block_count();
+    let Some(entries) = read_mbr(dev, 0) else {
+        return Vec::new();
+    };
+
+    let mut res = Vec::new();
+
+    for (i, (kind, start, count)) in entries.iter().copied().enumerate() {
+        if kind == TYPE_GPT_PROTECTIVE {
+            return read_gpt(dev).unwrap_or_default();
+        }
+
+        if TYPES_EXTENDED.contains(&kind) {
+            read_logical(dev, start, &mut res);
+        } else if kind != TYPE_EMPTY {
+            res.push(PartitionEntry {
+                number: i as u32 + 1,
+                start,
+                count,
+            });
+        }
+    }
+
+    res.retain(|p| {
+        p.count > 0 && p.start > 0 && p.start as u64 + p.count as u64 <= block_count as u64
+    });
+    res
+}
+
+// (type, start, count) of the 4 entries of the table stored at `addr`
+fn read_mbr(dev: &mut dyn BlockDevice, addr: u32) -> Option<[(u8, u32, u32); 4]> {
+    let block = Block::read(dev, addr).ok()?;
+    let data = block.data();
+
+    if data[510..512] != MBR_SIGNATURE {
+        return None;
+    }
+
+    let mut res = [(TYPE_EMPTY, 0, 0); 4];
+
+    for (i, entry) in res.iter_mut().enumerate() {
+        let raw = &data[MBR_TABLE_OFFSET + i * MBR_ENTRY_SIZE..][..MBR_ENTRY_SIZE];
+
+        // boot code without a partition table is unlikely to only have these in the status bytes
+        if raw[0] != 0x00 && raw[0] != 0x80 {
+            return None;
+        }
+
+        *entry = (raw[4], get_u32(raw, 8), get_u32(raw, 12));
+    }
+
+    Some(res)
+}
+
+// each extended boot record describes a logical partition and links to the next record
+fn read_logical(dev: &mut dyn BlockDevice, ext_start: u32, res: &mut Vec<PartitionEntry>) {
+    let mut addr = ext_start;
+
+    for n in 0..MAX_LOGICAL {
+        let Some(entries) = read_mbr(dev, addr) else {
+            return;
+        };
+
+        let (kind, start, count) = entries[0];
+
+        if kind != TYPE_EMPTY {
+            res.push(PartitionEntry {
+                number: 5 + n as u32,
+                start: addr + start,
+                count,
+            });
+        }
+
+        match entries[1] {
+            (TYPE_EMPTY, _, _) | (_, 0, _) => return,
+            (_, next, _) => addr = ext_start + next,
+        }
+    }
+}
+
+fn read_gpt(dev: &mut dyn BlockDevice) -> Option<Vec<PartitionEntry>> {
+    let header = Block::read(dev, GPT_HEADER_ADDR).ok()?;
+    let data = header.data();
+
+    if &data[0..8] != GPT_SIGNATURE {
+        return None;
+    }
+
+    let table_addr = u32::try_from(get_u64(data, 72)).ok()?;
+    let entry_count = get_u32(data, 80);
+    let entry_size = get_u32(data, 84) as usize;
+    let block_size = dev.block_size();
+
+    if entry_size < 128 || !block_size.is_multiple_of(entry_size) {
+        return None;
+    }
+
+    let per_block = block_size / entry_size;
+    let mut res = Vec::new();
+    let mut block = None;
+
+    for i in 0..entry_count as usize {
+        if i % per_block == 0 {
+            block = Some(Block::read(dev, table_addr + (i / per_block) as u32).ok()?);
+        }
+
+        let raw = &block.as_ref()?.data()[i % per_block * entry_size..][..entry_size];
+
+        // unused entries have a null type GUID
+        if raw[0..16].iter().all(|&b| b == 0) {
+            continue;
+        }
+
+        let (Ok(first), Ok(last)) = (
+            u32::try_from(get_u64(raw, 32)),
+            u32::try_from(get_u64(raw, 40)),
+        ) else {
+            continue;
+        };
+
+        if last >= first {
+            res.push(PartitionEntry {
+                number: i as u32 + 1,
+                start: first,
+                count: last - first + 1,
+            });
+        }
+    }
+
+    Some(res)
+}
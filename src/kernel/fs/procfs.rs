@@ -0,0 +1,294 @@
+use super::{DirEntry, Directory, File, FileKind, FileSystem};
+use crate::kernel::{
+    arch::{
+        interrupts::{self, IRQS},
+        mem::{self, allocator, FRAME_SIZE},
+    },
+    process::{Process, MAX_PROC_SIZE, PROCESSES},
+    resource::{Device, Resource},
+};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write;
+use raw_cpuid::CpuId;
+
+// files found at the top of the filesystem
+const GLOBAL_FILES: [&str; 4] = ["meminfo", "uptime", "interrupts", "cpuinfo"];
+
+// files found in the directory of each process
+const PROCESS_FILES: [&str; 6] = ["status", "cwd", "env", "handles", "memory", "threads"];
+
+// the low byte of an address is the index of the file plus 1 and the rest is the PID plus 1,
+// which leaves 0 for the root
+const PID_SHIFT: u32 = 8;
+
+/// Exposes the state of the kernel as text files, usually mounted on `/proc`.
+///
+/// The content of a file is generated each time it is read, so sizes are reported as 0.
+pub(crate) struct ProcFs;
+
+impl FileSystem for ProcFs {
+    fn root(&self) -> u32 {
+        0
+    }
+
+    fn entries(&mut self, dir: &Directory) -> Result<Vec<DirEntry>, ()> {
+        let entry = |name: String, addr: u32, kind: FileKind| DirEntry {
+            dir: dir.clone(),
+            name,
+            addr,
+            size: 0,
+            kind,
+        };
+
+        if dir.addr != 0 {
+            let files = PROCESS_FILES.iter().enumerate();
+
+            return Ok(files
+                .map(|(i, name)| entry(name.to_string(), dir.addr | (i as u32 + 1), FileKind::File))
+                .collect());
+        }
+
+        let mut entries: Vec<_> = GLOBAL_FILES
+            .iter()
+            .enumerate()
+            .map(|(i, name)| entry(name.to_string(), i as u32 + 1, FileKind::File))
+            .collect();
+
+        for proc in processes(&PROCESSES.read()[..]) {
+            let pid = proc.id().inner();
+            entries.push(entry(
+                pid.to_string(),
+                (pid as u32 + 1) << PID_SHIFT,
+                FileKind::Dir,
+            ));
+        }
+
+        Ok(entries)
+    }
+
+    fn read(&mut self, file: &File, buf: &mut [u8]) -> Result<usize, ()> {
+        let content = generate(file.addr).ok_or(())?;
+        let content = content.as_bytes();
+        let start = (file.offset as usize).min(content.len());
+        let n = buf.len().min(content.len() - start);
+
+        buf[..n].copy_from_slice(&content[start..start + n]);
+        Ok(n)
+    }
+}
+
+// slots that were never handed out hold a process without a parent, except the first one
+// which belongs to the kernel
+fn processes(procs: &[Box<Process>]) -> impl Iterator<Item = &Process> {
+    procs
+        .iter()
+        .enumerate()
+        .filter(|(i, proc)| *i == 0 || proc.parent_id().is_some())
+        .map(|(_, proc)| proc.as_ref())
+}
+
+fn generate(addr: u32) -> Option<String> {
+    let index = (addr & ((1 << PID_SHIFT) - 1)).checked_sub(1)? as usize;
+
+    match (addr >> PID_SHIFT).checked_sub(1) {
+        None => match *GLOBAL_FILES.get(index)? {
+            "meminfo" => Some(meminfo()),
+            "uptime" => Some(uptime()),
+            "interrupts" => Some(interrupts()),
+            "cpuinfo" => Some(cpuinfo()),
+            _ => None,
+        },
+        Some(pid) => {
+            let procs = PROCESSES.read();
+            let proc = processes(&procs[..]).find(|proc| proc.id().inner() == pid as u64)?;
+
+            match *PROCESS_FILES.get(index)? {
+                "status" => Some(status(proc)),
+                "cwd" => Some(format!("{}\n", proc.dir())),
+                "env" => Some(env(proc)),
+                "handles" => Some(handles(proc)),
+                "memory" => Some(memory(proc)),
+                "threads" => Some(threads(proc)),
+                _ => None,
+            }
+        }
+    }
+}
+
+fn meminfo() -> String {
+    let total = mem::total_frames();
+    let used = mem::allocated_frames().min(total);
+    let heap_used = allocator::heap_used();
+
+    format!(
+        "total: {} KiB\nused: {} KiB\nfree: {} KiB\nheap total: {} KiB\nheap used: {} KiB\n",
+        total * FRAME_SIZE / 1024,
+        used * FRAME_SIZE / 1024,
+        (total - used) * FRAME_SIZE / 1024,
+        allocator::HEAP_SIZE / 1024,
+        heap_used / 1024,
+    )
+}
+
+fn uptime() -> String {
+    let ms = interrupts::uptime_ms();
+    format!("{}.{:03}\n", ms / 1000, ms % 1000)
+}
+
+fn interrupts() -> String {
+    let mut res = String::new();
+
+    for (irq, name) in IRQS {
+        writeln!(
+            res,
+            "{:>3} {:>10} {}",
+            irq,
+            interrupts::irq_count(irq),
+            name
+        )
+        .ok();
+    }
+
+    res
+}
+
+fn cpuinfo() -> String {
+    let cpuid = CpuId::new();
+    let mut res = String::new();
+
+    if let Some(vendor) = cpuid.get_vendor_info() {
+        writeln!(res, "vendor: {}", vendor.as_str()).ok();
+    }
+
+    if let Some(brand) = cpuid.get_processor_brand_string() {
+        writeln!(res, "model name: {}", brand.as_str().trim()).ok();
+    }
+
+    if let Some(info) = cpuid.get_feature_info() {
+        writeln!(res, "family: {}", info.family_id()).ok();
+        writeln!(res, "model: {}", info.model_id()).ok();
+        writeln!(res, "stepping: {}", info.stepping_id()).ok();
+
+        let features = [
+            ("fpu", info.has_fpu()),
+            ("tsc", info.has_tsc()),
+            ("pae", info.has_pae()),
+            ("apic", info.has_apic()),
+            ("sse", info.has_sse()),
+            ("sse2", info.has_sse2()),
+            ("sse3", info.has_sse3()),
+            ("sse4_1", info.has_sse41()),
+            ("sse4_2", info.has_sse42()),
+            ("avx", info.has_avx()),
+            ("rdrand", info.has_rdrand()),
+            ("x2apic", info.has_x2apic()),
+            ("hypervisor", info.has_hypervisor()),
+        ];
+
+        let names: Vec<_> = features
+            .iter()
+            .filter(|(_, has)| *has)
+            .map(|(name, _)| *name)
+            .collect();
+
+        writeln!(res, "flags: {}", names.join(" ")).ok();
+    }
+
+    res
+}
+
+fn status(proc: &Process) -> String {
+    let parent = match proc.parent_id() {
+        Some(pid) => pid.inner().to_string(),
+        None => "-".into(),
+    };
+
+    let children: Vec<_> = proc.children().map(|pid| pid.inner().to_string()).collect();
+
+    format!(
+        "pid: {}\nparent: {}\nchildren: {}\nuser: {}\ngroup: {}\nprivileges: {:#x}\nthreads: {}\nhandles: {}\n",
+        proc.id().inner(),
+        parent,
+        children.join(" "),
+        proc.user().inner(),
+        proc.group().inner(),
+        proc.privileges().bits(),
+        proc.threads().count(),
+        proc.handles().count(),
+    )
+}
+
+fn env(proc: &Process) -> String {
+    let mut res = String::new();
+
+    for (key, value) in proc.env() {
+        writeln!(res, "{}={}", key, value).ok();
+    }
+
+    res
+}
+
+fn handles(proc: &Process) -> String {
+    let mut res = String::new();
+
+    for (handle, resource) in proc.handles() {
+        let desc = match &resource {
+            Resource::File(file) => format!("file {}", file.name()),
+            Resource::Directory(dir) => format!("dir {}", dir.name()),
+            Resource::Device(dev) => format!("device {}", device_name(dev)),
+            Resource::Pipe(p) if p.is_reader() => "pipe read".into(),
+            Resource::Pipe(_) => "pipe write".into(),
+            Resource::SharedMemory(shm) if shm.name().is_empty() => "shm".into(),
+            Resource::SharedMemory(shm) => format!("shm {}", shm.name()),
+            Resource::Channel(_) => "channel".into(),
+        };
+
+        writeln!(res, "{} {}", handle, desc).ok();
+    }
+
+    res
+}
+
+fn device_name(dev: &Device) -> String {
+    match dev {
+        Device::Null => "null".into(),
+        Device::Zero => "zero".into(),
+        Device::Random => "random".into(),
+        Device::Console(c) => format!("tty{}", c.vt() + 1),
+        Device::Serial => "ttyS0".into(),
+        Device::Disk(disk) => disk.name().into(),
+    }
+}
+
+fn memory(proc: &Process) -> String {
+    let code = proc.code_addr();
+
+    let mut res = format!(
+        "code: {:#x}-{:#x}\nstack: {:#x}\nentry: {:#x}\n",
+        code,
+        code + MAX_PROC_SIZE as u64,
+        proc.stack_addr(),
+        proc.entry_point_addr(),
+    );
+
+    for (addr, shm) in proc.mappings() {
+        writeln!(res, "shm: {:#x}-{:#x}", addr, addr + shm.size() as u64).ok();
+    }
+
+    res
+}
+
+fn threads(proc: &Process) -> String {
+    let mut res = String::new();
+
+    for thread in proc.threads() {
+        writeln!(res, "{}", thread.id().inner()).ok();
+    }
+
+    res
+}
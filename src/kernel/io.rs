@@ -1,9 +1,12 @@
+pub(crate) mod ata;
 pub(crate) mod console;
 pub(crate) mod keyboard;
+pub(crate) mod random;
 pub(crate) mod serial;
+pub(crate) mod tty;
 pub(crate) mod vga;
 
-use super::syscall;
+use super::{resource::DeviceRequest, syscall};
 use alloc::{
     format,
     string::{String, ToString},
@@ -11,6 +14,7 @@ use alloc::{
 use console::Style;
 use lazy_static::lazy_static;
 use spin::{Mutex, MutexGuard};
+use tty::{Termios, TERMIOS_SIZE};
 use vte::Parser;
 
 lazy_static! {
@@ -27,14 +31,23 @@ impl Stdin {
         Self {}
     }
 
+    /// Reads a key as soon as it is typed, without echoing it.
     pub fn read_char(&self, buf: &mut [u8]) -> Option<char> {
-        let Some(bytes) = syscall::read(0, buf) else {
-            return None;
-        };
+        let mut saved = [0; TERMIOS_SIZE];
+        syscall::ioctl(0, DeviceRequest::TermGetAttr, &mut saved)?;
+
+        let mut cbreak = Termios::from_bytes(&saved)?.cbreak().to_bytes();
+        syscall::ioctl(0, DeviceRequest::TermSetAttr, &mut cbreak)?;
+        let bytes = syscall::read(0, buf);
+        syscall::ioctl(0, DeviceRequest::TermSetAttr, &mut saved);
+
+        let bytes = bytes?;
 
         (bytes > 0).then(|| {
-            buf.to_vec().resize(bytes, 0);
-            String::from_utf8_lossy(buf).to_string().remove(0)
+            String::from_utf8_lossy(&buf[..bytes])
+                .chars()
+                .next()
+                .unwrap_or_default()
         })
     }
 
@@ -56,7 +69,7 @@ impl Stdout {
     }
 
     pub fn write(&self, s: &str) {
-        syscall::write(1, s.as_bytes());
+        syscall::write(1, s.as_bytes()).ok();
     }
 }
 
@@ -68,7 +81,7 @@ impl Stderr {
     }
 
     pub fn write(&self, s: &str) {
-        syscall::write(2, s.as_bytes());
+        syscall::write(2, s.as_bytes()).ok();
     }
 }
 
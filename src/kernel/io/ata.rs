@@ -0,0 +1,314 @@
+use crate::kernel::{
+    fs::block::{BlockDevice, BLOCK_SIZE},
+    io::{kprint, recoverable},
+};
+use alloc::{format, string::String, vec::Vec};
+use bit_field::BitField;
+use spin::Mutex;
+use x86_64::instructions::port::{Port, PortReadOnly, PortWriteOnly};
+
+const PRIMARY_IO_BASE: u16 = 0x1F0;
+const PRIMARY_CTRL_BASE: u16 = 0x3F6;
+const SECONDARY_IO_BASE: u16 = 0x170;
+const SECONDARY_CTRL_BASE: u16 = 0x376;
+
+// how many status reads to do before considering the drive unresponsive
+const TIMEOUT: usize = 1_000_000;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy)]
+enum Command {
+    Read = 0x20,
+    Write = 0x30,
+    CacheFlush = 0xE7,
+    Identify = 0xEC,
+}
+
+#[repr(usize)]
+#[derive(Debug, Clone, Copy)]
+enum Status {
+    Err = 0,
+    Drq = 3,
+    Df = 5,
+    Rdy = 6,
+    Bsy = 7,
+}
+
+static BUSES: Mutex<Vec<Bus>> = Mutex::new(Vec::new());
+
+pub(crate) fn init() {
+    {
+        let mut buses = BUSES.lock();
+        buses.push(Bus::new(0, PRIMARY_IO_BASE, PRIMARY_CTRL_BASE));
+        buses.push(Bus::new(1, SECONDARY_IO_BASE, SECONDARY_CTRL_BASE));
+    }
+
+    for drive in drives() {
+        kprint!(
+            "ATA {}:{} {} ({} KiB)\n",
+            drive.bus,
+            drive.dsk,
+            drive.model,
+            drive.block_count as usize * BLOCK_SIZE / 1024
+        );
+    }
+}
+
+struct Bus {
+    id: u8,
+    data: Port<u16>,
+    error: PortReadOnly<u8>,
+    sector_count: Port<u8>,
+    lba0: Port<u8>,
+    lba1: Port<u8>,
+    lba2: Port<u8>,
+    drive: Port<u8>,
+    status: PortReadOnly<u8>,
+    command: PortWriteOnly<u8>,
+    alt_status: PortReadOnly<u8>,
+    control: PortWriteOnly<u8>,
+}
+
+impl Bus {
+    fn new(id: u8, io_base: u16, ctrl_base: u16) -> Self {
+        let mut bus = Self {
+            id,
+            data: Port::new(io_base),
+            error: PortReadOnly::new(io_base + 1),
+            sector_count: Port::new(io_base + 2),
+            lba0: Port::new(io_base + 3),
+            lba1: Port::new(io_base + 4),
+            lba2: Port::new(io_base + 5),
+            drive: Port::new(io_base + 6),
+            status: PortReadOnly::new(io_base + 7),
+            command: PortWriteOnly::new(io_base + 7),
+            alt_status: PortReadOnly::new(ctrl_base),
+            control: PortWriteOnly::new(ctrl_base),
+        };
+
+        // we poll the drives, so tell them not to raise IRQ 14 and 15 (nIEN)
+        unsafe { bus.control.write(0x02) };
+
+        bus
+    }
+
+    fn status(&mut self) -> u8 {
+        unsafe { self.alt_status.read() }
+    }
+
+    fn is_set(&mut self, bit: Status) -> bool {
+        self.status().get_bit(bit as usize)
+    }
+
+    // reading the alternate status register takes ~100ns, four reads is the delay the spec asks
+    // for after selecting a drive
+    fn wait_400ns(&mut self) {
+        for _ in 0..4 {
+            self.status();
+        }
+    }
+
+    fn poll(&mut self, bit: Status, val: bool) -> Result<(), ()> {
+        for _ in 0..TIMEOUT {
+            if self.is_set(bit) == val {
+                return Ok(());
+            }
+        }
+
+        Err(())
+    }
+
+    fn select_drive(&mut self, dsk: u8) -> Result<(), ()> {
+        self.poll(Status::Bsy, false)?;
+        self.poll(Status::Drq, false)?;
+
+        unsafe { self.drive.write(0xA0 | (dsk << 4)) };
+        self.wait_400ns();
+
+        self.poll(Status::Bsy, false)?;
+        self.poll(Status::Drq, false)
+    }
+
+    fn setup_pio(&mut self, dsk: u8, addr: u32) -> Result<(), ()> {
+        self.select_drive(dsk)?;
+
+        unsafe {
+            self.drive
+                .write(0xE0 | (dsk << 4) | (addr.get_bits(24..28) as u8));
+            self.sector_count.write(1);
+            self.lba0.write(addr.get_bits(0..8) as u8);
+            self.lba1.write(addr.get_bits(8..16) as u8);
+            self.lba2.write(addr.get_bits(16..24) as u8);
+        }
+
+        Ok(())
+    }
+
+    fn write_command(&mut self, cmd: Command) -> Result<(), ()> {
+        unsafe { self.command.write(cmd as u8) };
+        self.wait_400ns();
+
+        // a floating bus reads as 0xFF and a missing drive as 0x00
+        if self.status() == 0 {
+            return Err(());
+        }
+
+        if self.is_set(Status::Err) || self.is_set(Status::Df) {
+            let err = unsafe { self.error.read() };
+            recoverable!("ATA {} error {:#X}", self.id, err);
+            return Err(());
+        }
+
+        self.poll(Status::Bsy, false)?;
+
+        if self.is_set(Status::Err) || self.is_set(Status::Df) {
+            return Err(());
+        }
+
+        Ok(())
+    }
+
+    fn identify(&mut self, dsk: u8) -> Option<[u16; 256]> {
+        if self.status() == 0xFF {
+            return None; // no drive on this bus
+        }
+
+        self.select_drive(dsk).ok()?;
+
+        unsafe {
+            self.sector_count.write(0);
+            self.lba0.write(0);
+            self.lba1.write(0);
+            self.lba2.write(0);
+        }
+
+        self.write_command(Command::Identify).ok()?;
+
+        // ATAPI and SATA devices answer the identify command with a signature
+        if unsafe { self.lba1.read() } != 0 || unsafe { self.lba2.read() } != 0 {
+            return None;
+        }
+
+        self.poll(Status::Drq, true).ok()?;
+
+        let mut res = [0; 256];
+
+        for word in res.iter_mut() {
+            *word = unsafe { self.data.read() };
+        }
+
+        Some(res)
+    }
+
+    fn read(&mut self, dsk: u8, addr: u32, buf: &mut [u8]) -> Result<(), ()> {
+        self.setup_pio(dsk, addr)?;
+        self.write_command(Command::Read)?;
+        self.poll(Status::Drq, true)?;
+
+        for chunk in buf[..BLOCK_SIZE].chunks_mut(2) {
+            let word = unsafe { self.data.read() };
+            chunk[0] = word.get_bits(0..8) as u8;
+            chunk[1] = word.get_bits(8..16) as u8;
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, dsk: u8, addr: u32, buf: &[u8]) -> Result<(), ()> {
+        self.setup_pio(dsk, addr)?;
+        self.write_command(Command::Write)?;
+        self.poll(Status::Drq, true)?;
+
+        for chunk in buf[..BLOCK_SIZE].chunks(2) {
+            let word = (chunk[1] as u16) << 8 | chunk[0] as u16;
+            unsafe { self.data.write(word) };
+        }
+
+        self.write_command(Command::CacheFlush)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Drive {
+    bus: u8,
+    dsk: u8,
+    block_count: u32,
+    model: String,
+}
+
+impl Drive {
+    pub(crate) fn open(bus: u8, dsk: u8) -> Option<Self> {
+        let mut buses = BUSES.lock();
+        let res = buses.get_mut(bus as usize)?.identify(dsk)?;
+
+        // words 60 and 61 hold the number of sectors addressable with 28-bit LBA
+        let block_count = (res[61] as u32) << 16 | res[60] as u32;
+
+        // the model string is stored as big endian words in 27..47
+        let model = res[27..47]
+            .iter()
+            .flat_map(|w| [(w >> 8) as u8 as char, (w & 0xFF) as u8 as char])
+            .collect::<String>()
+            .trim()
+            .into();
+
+        Some(Self {
+            bus,
+            dsk,
+            block_count,
+            model,
+        })
+    }
+
+    pub(crate) fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Short name of the drive, numbered from the primary master.
+    pub(crate) fn name(&self) -> String {
+        format!("ata{}", self.bus * 2 + self.dsk)
+    }
+}
+
+impl BlockDevice for Drive {
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn block_count(&self) -> u32 {
+        self.block_count
+    }
+
+    fn read_block(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), ()> {
+        if addr >= self.block_count || buf.len() < BLOCK_SIZE {
+            return Err(());
+        }
+
+        let mut buses = BUSES.lock();
+        buses[self.bus as usize].read(self.dsk, addr, buf)
+    }
+
+    fn write_block(&mut self, addr: u32, buf: &[u8]) -> Result<(), ()> {
+        if addr >= self.block_count || buf.len() < BLOCK_SIZE {
+            return Err(());
+        }
+
+        let mut buses = BUSES.lock();
+        buses[self.bus as usize].write(self.dsk, addr, buf)
+    }
+}
+
+/// Every ATA drive attached to the primary and secondary buses.
+pub(crate) fn drives() -> Vec<Drive> {
+    let mut res = Vec::new();
+
+    for bus in 0..2 {
+        for dsk in 0..2 {
+            if let Some(drive) = Drive::open(bus, dsk) {
+                res.push(drive);
+            }
+        }
+    }
+
+    res
+}
@@ -1,14 +1,11 @@
-#[cfg(target_arch = "x86_64")]
-use crate::kernel::arch::interrupts::halt;
-
+use super::tty::{self, LocalFlag, Signal};
 use crate::kernel::{fs::FileIO, io::kprint};
-use alloc::string::{String, ToString};
+use alloc::string::String;
+use bitflags::bitflags;
 use core::{
     fmt,
-    sync::atomic::{AtomicBool, Ordering},
+    sync::atomic::{AtomicUsize, Ordering},
 };
-use spin::Mutex;
-use x86_64::instructions::interrupts as x86_64cint; // x86_64 crate interrupts
 
 #[derive(Clone, Copy)]
 pub struct Style {
@@ -100,167 +97,164 @@ impl fmt::Display for Style {
     }
 }
 
-static INPUT: Mutex<String> = Mutex::new(String::new());
-static ECHO: AtomicBool = AtomicBool::new(true);
-static RAW: AtomicBool = AtomicBool::new(false);
-
-pub(super) fn switch_echo(cmd: &str) {
-    match cmd {
-        "enable" => ECHO.store(true, Ordering::SeqCst),
-        "disable" => ECHO.store(false, Ordering::SeqCst),
-        _ => (),
+bitflags! {
+    /// How the console treats what is typed, as queried and changed through the IOCTL syscall.
+    /// This is a shorthand for the settings of its line discipline.
+    pub(crate) struct TermMode: u8 {
+        const ECHO = 1; // typed keys are printed
+        const RAW = 1 << 1; // keys are read as soon as typed instead of by lines
     }
 }
 
-fn switch_raw(cmd: &str) {
-    match cmd {
-        "enable" => RAW.store(true, Ordering::SeqCst),
-        "disable" => RAW.store(false, Ordering::SeqCst),
-        _ => (),
+/// Virtual terminals, shown with Alt+F1 to Alt+F6. The kernel prints on the first one.
+pub(crate) const VT_COUNT: usize = 6;
+
+// the virtual terminal shown, which gets the keys typed
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn active() -> usize {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Shows the virtual terminal `vt`, the keys typed go to it from now on.
+pub(crate) fn switch_to(vt: usize) {
+    if vt >= VT_COUNT {
+        return;
+    }
+
+    ACTIVE.store(vt, Ordering::SeqCst);
+
+    #[cfg(feature = "vga")]
+    {
+        super::vga::show(vt);
     }
 }
 
-fn is_enabled(mode: &str) -> bool {
-    match mode {
-        "echo" => ECHO.load(Ordering::SeqCst),
-        "raw" => RAW.load(Ordering::SeqCst),
-        _ => false,
+pub(crate) fn switch_echo(vt: usize, cmd: &str) {
+    let console = Console::with_vt(vt);
+    let mode = console.mode();
+
+    match cmd {
+        "enable" => console.set_mode(mode | TermMode::ECHO),
+        "disable" => console.set_mode(mode - TermMode::ECHO),
+        _ => (),
     }
 }
 
+/// A virtual terminal, read through its line discipline.
 #[derive(Debug, Clone)]
-pub(crate) struct Console;
+pub(crate) struct Console {
+    vt: usize,
+}
 
 impl Console {
+    /// The first virtual terminal, where the kernel prints.
     pub(crate) fn new() -> Self {
-        Self {}
+        Self { vt: 0 }
+    }
+
+    pub(crate) fn with_vt(vt: usize) -> Self {
+        Self { vt }
+    }
+
+    pub(crate) const fn vt(&self) -> usize {
+        self.vt
     }
 
-    fn read_char() -> char {
-        switch_echo("disable");
-        switch_raw("enable");
+    pub(crate) fn mode(&self) -> TermMode {
+        let lflag = tty::termios(self.vt).lflag();
+        let mut mode = TermMode::empty();
 
-        loop {
-            halt();
+        mode.set(TermMode::ECHO, lflag.contains(LocalFlag::ECHO));
+        mode.set(TermMode::RAW, !lflag.contains(LocalFlag::ICANON));
+        mode
+    }
 
-            let res = x86_64cint::without_interrupts(|| {
-                let mut inp = INPUT.lock();
+    pub(crate) fn set_mode(&self, mode: TermMode) {
+        let mut termios = tty::termios(self.vt);
+        let mut lflag = termios.lflag();
 
-                if !inp.is_empty() {
-                    Some(inp.remove(0))
-                } else {
-                    None
-                }
-            });
+        lflag.set(LocalFlag::ECHO, mode.contains(TermMode::ECHO));
+        lflag.set(
+            LocalFlag::ICANON | LocalFlag::ISIG,
+            !mode.contains(TermMode::RAW),
+        );
 
-            if let Some(c) = res {
-                switch_echo("enable");
-                switch_raw("disable");
-                return c;
-            }
-        }
+        termios.set_lflag(lflag);
+        tty::set_termios(self.vt, termios);
     }
 
-    fn read_line() -> String {
-        loop {
-            halt();
-
-            let res = x86_64cint::without_interrupts(|| {
-                let mut inp = INPUT.lock();
-
-                match inp.chars().next_back() {
-                    Some('\n') => {
-                        let line = inp.clone();
-                        inp.clear();
-                        Some(line)
-                    }
-                    _ => None,
-                }
-            });
-
-            if let Some(line) = res {
-                return line;
-            }
-        }
+    /// Whether reading would return without waiting.
+    pub(crate) fn has_input(&self) -> bool {
+        tty::has_input(self.vt)
+    }
+
+    /// Reads what the line discipline gives, failing with the signal raised by a control
+    /// character meanwhile.
+    pub(crate) fn read_input(&mut self, buf: &mut [u8]) -> Result<usize, Signal> {
+        tty::read(self.vt, buf)
     }
 }
 
 impl FileIO for Console {
+    /// Same as `read_input`, without telling which signal was raised.
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
-        let mut s = if buf.len() == 4 {
-            Self::read_char().to_string()
-        } else {
-            Self::read_line()
-        };
-
-        s.truncate(buf.len());
-        let n = s.len();
-        buf[0..n].copy_from_slice(s.as_bytes());
-        Ok(n)
+        self.read_input(buf).map_err(|_| ())
     }
 
     fn write(&mut self, buf: &[u8]) -> Result<usize, ()> {
         let s = String::from_utf8_lossy(buf);
         let n = s.len();
-        kprint!("{s}");
+        print_to(self.vt, format_args!("{s}"));
         Ok(n)
     }
 }
 
-const ETXT: char = '\x03';
-const EOT: char = '\x04';
-const BACKSPACE: char = '\x08';
-const ESC: char = '\x1b';
-
+/// Handles a key typed on the keyboard, which goes to the virtual terminal shown.
 pub(crate) fn handle_key_inp(key: char) {
-    let mut inp = INPUT.lock();
-
-    if key == BACKSPACE && !is_enabled("raw") {
-        if let Some(c) = inp.pop() {
-            if is_enabled("echo") {
-                let n = match c {
-                    ETXT | EOT | ESC => 2,
-                    _ => {
-                        if (c as u32) < 0xFF {
-                            1
-                        } else {
-                            c.len_utf8()
-                        }
-                    }
-                };
-
-                console_print(format_args!("{}", BACKSPACE.to_string().repeat(n)));
-            }
-        }
-    } else {
-        let key = if (key as u32) < 0xFF {
-            (key as u8) as char
-        } else {
-            key
-        };
+    let vt = active();
 
-        inp.push(key);
+    #[cfg(feature = "vga")]
+    {
+        super::vga::scroll_to_bottom(vt);
+    }
 
-        if is_enabled("echo") {
-            match key {
-                ETXT => console_print(format_args!("^C")),
-                EOT => console_print(format_args!("^D")),
-                ESC => console_print(format_args!("^[")),
-                _ => console_print(format_args!("{key}")),
-            }
-        }
+    handle_key(vt, key);
+}
+
+/// Looks back in the history of the virtual terminal shown, or forward when `up` is false.
+pub(crate) fn scroll(up: bool) {
+    #[cfg(feature = "vga")]
+    {
+        super::vga::scroll(active(), up);
     }
 }
 
-#[doc(hidden)]
-pub fn console_print(args: fmt::Arguments) {
+/// Handles a key typed on the virtual terminal `vt`.
+pub(crate) fn handle_key(vt: usize, key: char) {
+    let echo = tty::input(vt, key);
+
+    if !echo.is_empty() {
+        print_to(vt, format_args!("{echo}"));
+    }
+}
+
+/// Prints on the virtual terminal `vt`, the first one being mirrored on the serial port.
+pub(crate) fn print_to(vt: usize, args: fmt::Arguments) {
     #[cfg(feature = "vga")]
     {
-        super::vga::vga_print(args);
+        super::vga::vga_print(vt, args);
     }
 
     #[cfg(feature = "serial")]
     {
-        super::serial::serial_print(args);
+        if vt == 0 {
+            super::serial::serial_print(args);
+        }
     }
 }
+
+#[doc(hidden)]
+pub fn console_print(args: fmt::Arguments) {
+    print_to(0, args);
+}
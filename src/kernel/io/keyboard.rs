@@ -3,7 +3,7 @@ use core::sync::atomic::AtomicBool;
 use super::console;
 use pc_keyboard::{layouts, DecodedKey, Error, HandleControl, KeyEvent, Keyboard, ScancodeSet1};
 use spin::Mutex;
-use x86_64::instructions::port::Port;
+use x86_64::instructions::{interrupts as x86_64cint, port::Port}; // x86_64 crate interrupts
 
 pub(crate) static KEYBOARD: Mutex<Option<KeyboardLayout>> = Mutex::new(None);
 pub(crate) static ALT: AtomicBool = AtomicBool::new(false);
@@ -11,7 +11,7 @@ pub(crate) static CTRL: AtomicBool = AtomicBool::new(false);
 pub(crate) static SHIFT: AtomicBool = AtomicBool::new(false);
 
 pub(crate) fn init() {
-    set_kbd_layout(option_env!("KBD_LAYOUT").unwrap_or("qwerty"));
+    set_kbd_layout(option_env!("KBD_LAYOUT").unwrap_or("qwerty")).ok();
 }
 
 pub(crate) enum KeyboardLayout {
@@ -49,10 +49,13 @@ impl KeyboardLayout {
     }
 }
 
-pub(crate) fn set_kbd_layout(layout: &str) {
-    if let Some(kbd) = KeyboardLayout::from(layout) {
-        *KEYBOARD.lock() = Some(kbd);
-    }
+/// Decodes the keys as `azerty`, `dvorak` or `qwerty` from now on.
+pub(crate) fn set_kbd_layout(layout: &str) -> Result<(), ()> {
+    let kbd = KeyboardLayout::from(layout).ok_or(())?;
+
+    // the keyboard interrupt takes the same lock
+    x86_64cint::without_interrupts(|| *KEYBOARD.lock() = Some(kbd));
+    Ok(())
 }
 
 pub(crate) fn read_scancode() -> u8 {
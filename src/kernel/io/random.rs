@@ -0,0 +1,28 @@
+use core::arch::x86_64::_rdtsc;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::random::RdRand;
+
+lazy_static! {
+    // CPUs without RDRAND fall back on a xorshift generator seeded with the timestamp counter
+    static ref STATE: Mutex<u64> = Mutex::new(unsafe { _rdtsc() } | 1);
+}
+
+pub(crate) fn get_u64() -> u64 {
+    if let Some(n) = RdRand::new().and_then(|rdrand| rdrand.get_u64()) {
+        return n;
+    }
+
+    let mut state = STATE.lock();
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+pub(crate) fn fill(buf: &mut [u8]) {
+    for chunk in buf.chunks_mut(8) {
+        let bytes = get_u64().to_ne_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
@@ -1,5 +1,10 @@
 use super::{console, PARSER};
-use core::fmt::{self, Write};
+use crate::kernel::scheduler;
+use alloc::collections::VecDeque;
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 use lazy_static::lazy_static;
 use spin::Mutex;
 use uart_16550::SerialPort;
@@ -12,8 +17,17 @@ pub(crate) fn init() {
 
 lazy_static! {
     pub(crate) static ref SERIAL: Mutex<Serial> = Mutex::new(Serial::new(0x3F8));
+    // bytes received while the port is open as a device, waiting to be read
+    static ref INPUT: Mutex<VecDeque<u8>> = Mutex::new(VecDeque::new());
 }
 
+/// Bytes received and kept for the readers of the port, the next ones are dropped.
+const INPUT_SIZE: usize = 4096;
+
+// descriptions of the port open as a device, the first virtual terminal gets what is received
+// while there are none
+static OPENED: AtomicUsize = AtomicUsize::new(0);
+
 pub(crate) struct Serial {
     port: SerialPort,
 }
@@ -33,7 +47,7 @@ impl Serial {
         self.port.receive()
     }
 
-    fn write_byte(&mut self, byte: u8) {
+    pub(crate) fn write_byte(&mut self, byte: u8) {
         self.port.send(byte);
     }
 }
@@ -44,7 +58,7 @@ impl Perform for Serial {
             'h' => {
                 for param in params.iter() {
                     match param[0] {
-                        12 => console::switch_echo("enable"),
+                        12 => console::switch_echo(0, "enable"),
                         _ => return,
                     }
                 }
@@ -52,7 +66,7 @@ impl Perform for Serial {
             'l' => {
                 for param in params.iter() {
                     match param[0] {
-                        12 => console::switch_echo("disable"),
+                        12 => console::switch_echo(0, "disable"),
                         _ => return,
                     }
                 }
@@ -84,3 +98,79 @@ pub(super) fn serial_print(args: fmt::Arguments) {
             .expect("Failed to write to serial port")
     });
 }
+
+/// Handles a byte received by the port, called from its interrupt handler.
+pub(crate) fn receive(byte: u8) {
+    if OPENED.load(Ordering::SeqCst) > 0 {
+        let mut input = INPUT.lock();
+
+        if input.len() < INPUT_SIZE {
+            input.push_back(byte);
+        }
+
+        return;
+    }
+
+    let key = match byte as char {
+        '\r' => '\n',
+        '\x7F' => '\x08',
+        c => c,
+    };
+
+    // the serial port is the first virtual terminal
+    console::handle_key(0, key);
+}
+
+/// Registers one more description of the port open as a device.
+pub(crate) fn open() {
+    OPENED.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Unregisters a description of the port, the bytes not read are dropped with the last one.
+pub(crate) fn close() {
+    let opened = OPENED
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .unwrap_or(0);
+
+    if opened <= 1 {
+        x86_64cint::without_interrupts(|| INPUT.lock().clear());
+    }
+}
+
+/// Whether reading the port would return without waiting.
+pub(crate) fn has_input() -> bool {
+    x86_64cint::without_interrupts(|| !INPUT.lock().is_empty())
+}
+
+/// Waits for bytes to be received then reads what is available.
+pub(crate) fn read(buf: &mut [u8]) -> usize {
+    let mut n = 0;
+
+    scheduler::wait_until(|| {
+        x86_64cint::without_interrupts(|| {
+            let mut input = INPUT.lock();
+            n = buf.len().min(input.len());
+
+            for (byte, b) in buf.iter_mut().zip(input.drain(..n)) {
+                *byte = b;
+            }
+
+            n > 0 || buf.is_empty()
+        })
+    });
+
+    n
+}
+
+/// Sends the bytes as they are, escape sequences included.
+pub(crate) fn write(buf: &[u8]) -> usize {
+    x86_64cint::without_interrupts(|| {
+        let mut serial = SERIAL.lock();
+
+        for &byte in buf {
+            serial.write_byte(byte);
+        }
+    });
+
+    buf.len()
+}
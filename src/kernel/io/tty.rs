@@ -0,0 +1,416 @@
+use super::console::VT_COUNT;
+use crate::kernel::scheduler;
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+use bitflags::bitflags;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts as x86_64cint; // x86_64 crate interrupts
+
+// positions of the control characters in `Termios`, a character of 0 being disabled
+pub(crate) const VINTR: usize = 0;
+pub(crate) const VQUIT: usize = 1;
+pub(crate) const VERASE: usize = 2;
+pub(crate) const VKILL: usize = 3;
+pub(crate) const VEOF: usize = 4;
+pub(crate) const VSUSP: usize = 5;
+pub(crate) const VWERASE: usize = 6;
+pub(crate) const VMIN: usize = 7; // bytes a read waits for outside of canonical mode
+pub(crate) const VTIME: usize = 8; // tenths of a second a read waits for outside of canonical mode
+const NCCS: usize = 9;
+
+/// Bytes taken by `Termios` once given to or taken from the IOCTL syscall.
+pub(crate) const TERMIOS_SIZE: usize = 1 + NCCS;
+
+lazy_static! {
+    // the line discipline of each virtual terminal
+    static ref TTYS: Mutex<Vec<Tty>> = Mutex::new((0..VT_COUNT).map(|_| Tty::new()).collect());
+}
+
+bitflags! {
+    pub(crate) struct LocalFlag: u8 {
+        const ECHO = 1; // typed keys are printed
+        const ICANON = 1 << 1; // input is edited and read by lines
+        const ISIG = 1 << 2; // the interrupt, quit and suspend characters raise signals
+    }
+}
+
+/// The settings of the line discipline, as with the `termios` structure of POSIX.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Termios {
+    lflag: LocalFlag,
+    cc: [u8; NCCS],
+}
+
+impl Termios {
+    /// Canonical mode with echo and signals, and the usual control characters.
+    pub(crate) const fn new() -> Self {
+        let mut cc = [0; NCCS];
+        cc[VINTR] = 0x03; // ^C
+        cc[VQUIT] = 0x1C; // ^\
+        cc[VERASE] = 0x08; // backspace
+        cc[VKILL] = 0x15; // ^U
+        cc[VEOF] = 0x04; // ^D
+        cc[VSUSP] = 0x1A; // ^Z
+        cc[VWERASE] = 0x17; // ^W
+        cc[VMIN] = 1;
+
+        let lflag = LocalFlag::ECHO.bits() | LocalFlag::ICANON.bits() | LocalFlag::ISIG.bits();
+
+        Self {
+            lflag: LocalFlag::from_bits_truncate(lflag),
+            cc,
+        }
+    }
+
+    pub(crate) const fn lflag(&self) -> LocalFlag {
+        self.lflag
+    }
+
+    pub(crate) fn set_lflag(&mut self, lflag: LocalFlag) {
+        self.lflag = lflag;
+    }
+
+    pub(crate) const fn cc(&self, i: usize) -> u8 {
+        self.cc[i]
+    }
+
+    pub(crate) fn set_cc(&mut self, i: usize, c: u8) {
+        self.cc[i] = c;
+    }
+
+    /// Keys are read one by one as soon as typed and without echo, signals are still raised.
+    pub(crate) fn cbreak(mut self) -> Self {
+        self.lflag.remove(LocalFlag::ICANON | LocalFlag::ECHO);
+        self.cc[VMIN] = 1;
+        self.cc[VTIME] = 0;
+        self
+    }
+
+    /// As `cbreak`, control characters being read like any other key.
+    pub(crate) fn raw(self) -> Self {
+        let mut termios = self.cbreak();
+        termios.lflag.remove(LocalFlag::ISIG);
+        termios
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; TERMIOS_SIZE] {
+        let mut bytes = [0; TERMIOS_SIZE];
+        bytes[0] = self.lflag.bits();
+        bytes[1..].copy_from_slice(&self.cc);
+        bytes
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..TERMIOS_SIZE)?;
+
+        let mut cc = [0; NCCS];
+        cc.copy_from_slice(&bytes[1..]);
+
+        Some(Self {
+            lflag: LocalFlag::from_bits(bytes[0])?,
+            cc,
+        })
+    }
+}
+
+/// What the interrupt, quit and suspend characters raise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Signal {
+    Interrupt,
+    Quit,
+    Suspend,
+}
+
+/// The line discipline of a terminal, turning typed keys into what its readers get.
+///
+/// In canonical mode keys are collected into a line that can be edited with the erase, kill
+/// and word erase characters, and only given to readers once ended by a newline or the EOF
+/// character, which alone on a line reads as the end of the input. Otherwise keys can be read
+/// as soon as typed, as `VMIN` and `VTIME` tell.
+#[derive(Debug)]
+pub(crate) struct Tty {
+    termios: Termios,
+    line: String,             // the line being edited in canonical mode
+    ready: VecDeque<Vec<u8>>, // lines or keys that can be read, an empty line being EOF
+    signal: Option<Signal>,   // raised and not yet given to a reader
+}
+
+impl Tty {
+    pub(crate) fn new() -> Self {
+        Self {
+            termios: Termios::new(),
+            line: String::new(),
+            ready: VecDeque::new(),
+            signal: None,
+        }
+    }
+
+    pub(crate) const fn termios(&self) -> Termios {
+        self.termios
+    }
+
+    /// Changes the settings, the line being edited can be read right away when leaving
+    /// canonical mode.
+    pub(crate) fn set_termios(&mut self, termios: Termios) {
+        if !termios.lflag.contains(LocalFlag::ICANON) && !self.line.is_empty() {
+            let line = core::mem::take(&mut self.line);
+            self.ready.push_back(line.into_bytes());
+        }
+
+        self.termios = termios;
+    }
+
+    /// Whether a read would return without waiting.
+    pub(crate) fn has_input(&self) -> bool {
+        !self.ready.is_empty() || self.signal.is_some()
+    }
+
+    pub(crate) fn take_signal(&mut self) -> Option<Signal> {
+        self.signal.take()
+    }
+
+    /// Handles a typed key, returning what has to be echoed.
+    pub(crate) fn input(&mut self, key: char) -> String {
+        let lflag = self.termios.lflag;
+        let echo = lflag.contains(LocalFlag::ECHO);
+
+        if let Some(signal) = self.signal_of(key) {
+            // whatever was typed before is dropped
+            self.line.clear();
+            self.ready.clear();
+            self.signal = Some(signal);
+
+            return match echo {
+                true => visible(key) + "\n",
+                false => String::new(),
+            };
+        }
+
+        if !lflag.contains(LocalFlag::ICANON) {
+            let mut bytes = [0; 4];
+            self.ready
+                .push_back(key.encode_utf8(&mut bytes).as_bytes().to_vec());
+
+            return match echo {
+                true => visible(key),
+                false => String::new(),
+            };
+        }
+
+        let erased = if self.is(key, VERASE) {
+            self.line.pop().into_iter().collect()
+        } else if self.is(key, VKILL) {
+            core::mem::take(&mut self.line)
+        } else if self.is(key, VWERASE) {
+            // the last word along with the spaces after it
+            let trimmed = self.line.trim_end();
+            let start = trimmed.rfind([' ', '\t']).map_or(0, |i| i + 1);
+            self.line.split_off(start)
+        } else if self.is(key, VEOF) {
+            // the line is given as is, nothing at all reading as the end of the input
+            let line = core::mem::take(&mut self.line);
+            self.ready.push_back(line.into_bytes());
+            return String::new();
+        } else {
+            self.line.push(key);
+
+            if key == '\n' {
+                let line = core::mem::take(&mut self.line);
+                self.ready.push_back(line.into_bytes());
+            }
+
+            return match echo {
+                true => visible(key),
+                false => String::new(),
+            };
+        };
+
+        match echo {
+            true => erased.chars().map(|c| "\x08".repeat(width(c))).collect(),
+            false => String::new(),
+        }
+    }
+
+    // whether `key` is the control character `i`
+    fn is(&self, key: char, i: usize) -> bool {
+        let c = self.termios.cc[i];
+        c != 0 && key as u32 == c as u32
+    }
+
+    fn signal_of(&self, key: char) -> Option<Signal> {
+        if !self.termios.lflag.contains(LocalFlag::ISIG) {
+            None
+        } else if self.is(key, VINTR) {
+            Some(Signal::Interrupt)
+        } else if self.is(key, VQUIT) {
+            Some(Signal::Quit)
+        } else if self.is(key, VSUSP) {
+            Some(Signal::Suspend)
+        } else {
+            None
+        }
+    }
+
+    /// Reads the first line ready, what doesn't fit in `buf` being left for the next read.
+    /// Returns `None` when no line is ready.
+    fn read_line(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let line = self.ready.front_mut()?;
+        let n = buf.len().min(line.len());
+
+        buf[..n].copy_from_slice(&line[..n]);
+        line.drain(..n);
+
+        if line.is_empty() {
+            self.ready.pop_front();
+        }
+
+        Some(n)
+    }
+
+    /// Reads as many of the keys ready as fit in `buf`, keys are only split when the first one
+    /// doesn't fit on its own.
+    fn read_keys(&mut self, buf: &mut [u8]) -> usize {
+        let mut n = 0;
+
+        while let Some(key) = self.ready.front_mut() {
+            if n + key.len() > buf.len() {
+                if n == 0 {
+                    n = buf.len();
+                    buf.copy_from_slice(&key[..n]);
+                    key.drain(..n);
+                }
+
+                break;
+            }
+
+            buf[n..n + key.len()].copy_from_slice(key);
+            n += key.len();
+            self.ready.pop_front();
+        }
+
+        n
+    }
+}
+
+// how a key is echoed, control characters being shown as `^C`
+fn visible(key: char) -> String {
+    match key {
+        '\n' | '\t' => key.into(),
+        c if (c as u32) < 0x20 => ['^', (c as u8 + b'@') as char].iter().collect(),
+        '\x7F' => "^?".into(),
+        c => c.into(),
+    }
+}
+
+// cells taken by a key once echoed, the console drawing any other character in one cell
+fn width(key: char) -> usize {
+    match key {
+        '\t' => 1,
+        c if (c as u32) < 0x20 || c == '\x7F' => 2,
+        _ => 1,
+    }
+}
+
+fn with_tty<T>(vt: usize, f: impl FnOnce(&mut Tty) -> T) -> T {
+    // the keyboard interrupt handler locks them as well
+    x86_64cint::without_interrupts(|| f(&mut TTYS.lock()[vt]))
+}
+
+pub(crate) fn termios(vt: usize) -> Termios {
+    with_tty(vt, |tty| tty.termios())
+}
+
+pub(crate) fn set_termios(vt: usize, termios: Termios) {
+    with_tty(vt, |tty| tty.set_termios(termios));
+}
+
+pub(crate) fn has_input(vt: usize) -> bool {
+    with_tty(vt, |tty| tty.has_input())
+}
+
+/// Handles a key typed on the virtual terminal `vt`, returning what has to be echoed.
+pub(crate) fn input(vt: usize, key: char) -> String {
+    // called from interrupt handlers
+    TTYS.lock()[vt].input(key)
+}
+
+// waits until `done` holds or `timeout` milliseconds have passed, failing as soon as a signal
+// is raised, which is then given to the reader
+fn wait_for(
+    vt: usize,
+    timeout: Option<u64>,
+    mut done: impl FnMut(&mut Tty) -> bool,
+) -> Result<bool, Signal> {
+    let mut signal = None;
+
+    let ready = scheduler::wait_until_timeout(
+        || {
+            with_tty(vt, |tty| {
+                signal = tty.take_signal();
+                signal.is_some() || done(tty)
+            })
+        },
+        timeout,
+    );
+
+    match signal {
+        Some(signal) => Err(signal),
+        None => Ok(ready),
+    }
+}
+
+/// Reads the virtual terminal `vt` as its line discipline tells, a line at a time in canonical
+/// mode. Fails with the signal raised meanwhile if there is one.
+pub(crate) fn read(vt: usize, buf: &mut [u8]) -> Result<usize, Signal> {
+    let termios = termios(vt);
+    let mut n = 0;
+
+    if termios.lflag.contains(LocalFlag::ICANON) {
+        wait_for(vt, None, |tty| {
+            tty.read_line(buf).map(|count| n = count).is_some()
+        })?;
+        return Ok(n);
+    }
+
+    let min = (termios.cc[VMIN] as usize).min(buf.len());
+    let time = termios.cc[VTIME] as u64 * 100;
+
+    match (min, time) {
+        (0, 0) => {
+            wait_for(vt, Some(0), |tty| {
+                n = tty.read_keys(buf);
+                true
+            })?;
+        }
+        (0, _) => {
+            wait_for(vt, Some(time), |tty| {
+                n = tty.read_keys(buf);
+                n > 0
+            })?;
+        }
+        (_, 0) => {
+            wait_for(vt, None, |tty| {
+                n += tty.read_keys(&mut buf[n..]);
+                n >= min
+            })?;
+        }
+        _ => {
+            // the timer starts with the first key and restarts with each of the next ones
+            while n < min {
+                let before = n;
+                let timeout = (n > 0).then_some(time);
+
+                let ready = wait_for(vt, timeout, |tty| {
+                    n += tty.read_keys(&mut buf[n..]);
+                    n > before
+                })?;
+
+                if !ready {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(n)
+}
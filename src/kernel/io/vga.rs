@@ -1,4 +1,5 @@
-use super::{console, PARSER};
+use super::console::{self, VT_COUNT};
+use alloc::{collections::VecDeque, vec::Vec};
 use bit_field::BitField;
 use core::{
     fmt::{self, Write},
@@ -7,7 +8,7 @@ use core::{
 use lazy_static::lazy_static;
 use spin::Mutex;
 use volatile::Volatile;
-use vte::{Params, Perform};
+use vte::{Params, Parser, Perform};
 use x86_64::instructions::{interrupts as x86_64cint, port::Port};
 
 pub(crate) fn init() {
@@ -29,7 +30,7 @@ pub(crate) fn init() {
     set_attr_ctrl_reg(0xF, 0x3F);
 
     x86_64cint::without_interrupts(|| {
-        WRITER.lock().set_palette(Palette::default());
+        SCREENS.lock()[0].writer.set_palette(Palette::default());
     });
 
     let reg = 0x10;
@@ -39,16 +40,21 @@ pub(crate) fn init() {
 
     set_underline_location(0x1F);
 
-    WRITER.lock().clear_screen();
+    for screen in SCREENS.lock().iter_mut() {
+        screen.writer.clear_screen();
+    }
 }
 
 lazy_static! {
-    static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        cursor: [0; 2],
-        writer: [0; 2],
-        cc: ColorCode::new(FG, BG),
-        buf: unsafe { &mut *(0xB8000 as *mut Buffer) },
-    });
+    // one for each virtual terminal, the first one being shown at first
+    static ref SCREENS: Mutex<Vec<Screen>> = Mutex::new(
+        (0..VT_COUNT)
+            .map(|vt| {
+                let buf = (vt == 0).then(|| unsafe { &mut *(0xB8000 as *mut Buffer) });
+                Screen::new(vt, buf)
+            })
+            .collect()
+    );
 }
 
 #[repr(u8)]
@@ -210,6 +216,11 @@ impl ops::DerefMut for SChar {
 const BUF_HEIGHT: usize = 25;
 const BUF_WIDTH: usize = 80;
 
+/// Lines scrolled off the top a terminal keeps at first, `set_scrollback` changes it.
+pub(crate) const SCROLLBACK: usize = 256;
+/// The most lines of history a terminal can keep.
+pub(crate) const MAX_SCROLLBACK: usize = 4096;
+
 #[repr(transparent)]
 struct Buffer {
     chars: [[Volatile<SChar>; BUF_WIDTH]; BUF_HEIGHT],
@@ -226,13 +237,97 @@ const INPUT_STATUS_REG: u16 = 0x3DA;
 const UNPRINTABLE: u8 = 0x00;
 
 struct Writer {
+    vt: usize, // the virtual terminal written to
     cursor: [usize; 2],
     writer: [usize; 2],
     cc: ColorCode,
-    buf: &'static mut Buffer,
+    cursor_on: bool,
+    chars: [[SChar; BUF_WIDTH]; BUF_HEIGHT], // the screen of the terminal
+    buf: Option<&'static mut Buffer>,        // the hardware buffer, while the terminal is shown
+    history: VecDeque<[SChar; BUF_WIDTH]>,   // the lines scrolled off the top, oldest first
+    scrollback: usize,                       // how many of them are kept
+    scroll: usize,                           // lines looked back in the history
 }
 
 impl Writer {
+    fn new(vt: usize, buf: Option<&'static mut Buffer>) -> Self {
+        let blank = SChar {
+            ascii: b' ',
+            cc: ColorCode::new(FG, BG),
+        };
+
+        Self {
+            vt,
+            cursor: [0; 2],
+            writer: [0; 2],
+            cc: ColorCode::new(FG, BG),
+            cursor_on: true,
+            chars: [[blank; BUF_WIDTH]; BUF_HEIGHT],
+            buf,
+            history: VecDeque::new(),
+            scrollback: SCROLLBACK,
+            scroll: 0,
+        }
+    }
+
+    // stores a character of the screen, which is drawn right away when the terminal is shown
+    // and not looking back in its history
+    fn put(&mut self, x: usize, y: usize, c: SChar) {
+        self.chars[y][x] = c;
+
+        if let (Some(buf), 0) = (&mut self.buf, self.scroll) {
+            buf.chars[y][x].write(c);
+        }
+    }
+
+    /// Draws the terminal in the hardware buffer, which is written to from now on.
+    fn show(&mut self, buf: &'static mut Buffer) {
+        self.buf = Some(buf);
+        self.render();
+        self.write_cursor();
+    }
+
+    // draws the rows seen `scroll` lines back in the history, when the terminal is shown
+    fn render(&mut self) {
+        let top = self.history.len() - self.scroll;
+
+        if let Some(buf) = &mut self.buf {
+            for (y, row) in buf.chars.iter_mut().enumerate() {
+                let line = match self.history.get(top + y) {
+                    Some(line) => line,
+                    None => &self.chars[top + y - self.history.len()],
+                };
+
+                for (cell, &c) in row.iter_mut().zip(line) {
+                    cell.write(c);
+                }
+            }
+        }
+
+        self.write_cursor_shape();
+    }
+
+    /// Looks `lines` back in the history, 0 being the screen as written.
+    fn scroll_to(&mut self, lines: usize) {
+        let lines = lines.min(self.history.len());
+
+        if lines != self.scroll {
+            self.scroll = lines;
+            self.render();
+        }
+    }
+
+    /// Keeps at most `lines` of history, forgetting the oldest ones.
+    fn set_scrollback(&mut self, lines: usize) {
+        self.scrollback = lines;
+
+        while self.history.len() > lines {
+            self.history.pop_front();
+        }
+
+        self.scroll_to(self.scroll);
+    }
+
     fn writer_pos(&self) -> (usize, usize) {
         (self.writer[0], self.writer[1])
     }
@@ -250,7 +345,12 @@ impl Writer {
         self.write_cursor();
     }
 
+    // the hardware cursor and its shape only follow the terminal shown
     fn write_cursor(&mut self) {
+        if self.buf.is_none() {
+            return;
+        }
+
         let pos = self.cursor[0] + self.cursor[1] * BUF_WIDTH;
         let mut addr = Port::new(CRTC_ADDR_REG);
         let mut data = Port::new(CRTC_DATA_REG);
@@ -263,7 +363,26 @@ impl Writer {
         }
     }
 
-    fn enable_cursor(&self) {
+    fn enable_cursor(&mut self) {
+        self.cursor_on = true;
+        self.write_cursor_shape();
+    }
+
+    fn disable_cursor(&mut self) {
+        self.cursor_on = false;
+        self.write_cursor_shape();
+    }
+
+    // the cursor is hidden while looking back in the history
+    fn write_cursor_shape(&self) {
+        if self.buf.is_none() {
+            return;
+        }
+
+        if !self.cursor_on || self.scroll > 0 {
+            return self.hide_cursor();
+        }
+
         let mut addr: Port<u8> = Port::new(CRTC_ADDR_REG);
         let mut data: Port<u8> = Port::new(CRTC_DATA_REG);
         let cursor_start = 13;
@@ -280,7 +399,7 @@ impl Writer {
         }
     }
 
-    fn disable_cursor(&self) {
+    fn hide_cursor(&self) {
         let mut addr = Port::new(CRTC_ADDR_REG);
         let mut data = Port::new(CRTC_DATA_REG);
 
@@ -291,7 +410,7 @@ impl Writer {
     }
 
     fn switch_echo(&self, cmd: &str) {
-        console::switch_echo(cmd);
+        console::switch_echo(self.vt, cmd);
     }
 
     fn write_byte(&mut self, byte: u8) {
@@ -306,7 +425,7 @@ impl Writer {
                         cc: self.cc,
                     };
                     let (x, y) = self.writer_pos();
-                    self.buf.chars[y][x].write(c);
+                    self.put(x, y, c);
                 }
             }
             byte => {
@@ -324,7 +443,7 @@ impl Writer {
 
                 let cc = self.cc;
                 let c = SChar { ascii, cc };
-                self.buf.chars[y][x].write(c);
+                self.put(x, y, c);
                 self.writer[0] += 1;
             }
         }
@@ -334,14 +453,35 @@ impl Writer {
         if self.writer[1] < BUF_HEIGHT - 1 {
             self.writer[1] += 1;
         } else {
+            // the view moves along with the output when the oldest line looked at is forgotten
+            let mut moved = false;
+
+            if self.scrollback > 0 {
+                if self.history.len() == self.scrollback {
+                    self.history.pop_front();
+                }
+
+                self.history.push_back(self.chars[0]);
+
+                if self.scroll > 0 {
+                    // keeps the lines looked at in place
+                    moved = self.scroll == self.history.len();
+                    self.scroll = (self.scroll + 1).min(self.history.len());
+                }
+            }
+
             for row in 1..BUF_HEIGHT {
                 for col in 0..BUF_WIDTH {
-                    let c = self.buf.chars[row][col].read();
-                    self.buf.chars[row - 1][col].write(c);
+                    let c = self.chars[row][col];
+                    self.put(col, row - 1, c);
                 }
             }
 
             self.clear_row_after(0, BUF_HEIGHT - 1);
+
+            if moved {
+                self.render();
+            }
         }
 
         self.writer[0] = 0;
@@ -358,7 +498,7 @@ impl Writer {
         };
 
         for col in x..BUF_WIDTH {
-            self.buf.chars[row][col].write(c);
+            self.put(col, row, c);
         }
     }
 
@@ -552,21 +692,88 @@ impl Perform for Writer {
     }
 }
 
-impl fmt::Write for Writer {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        let mut parser = PARSER.lock();
+/// What is written to a virtual terminal, along with the state of the escape sequences being
+/// parsed there.
+struct Screen {
+    writer: Writer,
+    parser: Parser,
+}
+
+impl Screen {
+    fn new(vt: usize, buf: Option<&'static mut Buffer>) -> Self {
+        Self {
+            writer: Writer::new(vt, buf),
+            parser: Parser::new(),
+        }
+    }
+}
 
+impl fmt::Write for Screen {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
         for byte in s.bytes() {
-            parser.advance(self, byte);
+            self.parser.advance(&mut self.writer, byte);
         }
 
-        let (x, y) = self.writer_pos();
-        self.set_cursor_pos(x, y);
+        let (x, y) = self.writer.writer_pos();
+        self.writer.set_cursor_pos(x, y);
 
         Ok(())
     }
 }
 
+/// Shows the screen of the virtual terminal `vt` instead of the one shown so far.
+pub(super) fn show(vt: usize) {
+    if vt >= VT_COUNT {
+        return;
+    }
+
+    x86_64cint::without_interrupts(|| {
+        let mut screens = SCREENS.lock();
+
+        if let Some(buf) = screens
+            .iter_mut()
+            .find_map(|screen| screen.writer.buf.take())
+        {
+            screens[vt].writer.show(buf);
+        }
+    });
+}
+
+/// Looks back in the history of the virtual terminal `vt` by half a screen, or forward when
+/// `up` is false.
+pub(super) fn scroll(vt: usize, up: bool) {
+    x86_64cint::without_interrupts(|| {
+        let writer = &mut SCREENS.lock()[vt].writer;
+        let scroll = match up {
+            true => writer.scroll + BUF_HEIGHT / 2,
+            false => writer.scroll.saturating_sub(BUF_HEIGHT / 2),
+        };
+
+        writer.scroll_to(scroll);
+    });
+}
+
+/// Gets the virtual terminal `vt` back to the screen as written, from its history.
+pub(super) fn scroll_to_bottom(vt: usize) {
+    x86_64cint::without_interrupts(|| SCREENS.lock()[vt].writer.scroll_to(0));
+}
+
+/// Changes how many lines scrolled off the top the virtual terminal `vt` keeps, up to
+/// `MAX_SCROLLBACK`.
+pub(crate) fn set_scrollback(vt: usize, lines: usize) -> Result<(), ()> {
+    if lines > MAX_SCROLLBACK {
+        return Err(());
+    }
+
+    x86_64cint::without_interrupts(|| SCREENS.lock()[vt].writer.set_scrollback(lines));
+    Ok(())
+}
+
+/// The columns then the rows of the screen.
+pub(crate) const fn size() -> (usize, usize) {
+    (BUF_WIDTH, BUF_HEIGHT)
+}
+
 fn is_printable(c: u8) -> bool {
     matches!(c, 0x20..=0x7E | 0x08 | 0x0A | 0x0D | 0x7F..=0xFF)
 }
@@ -616,10 +823,9 @@ fn set_underline_location(location: u8) {
 }
 
 #[doc(hidden)]
-pub(super) fn vga_print(args: fmt::Arguments) {
+pub(super) fn vga_print(vt: usize, args: fmt::Arguments) {
     x86_64cint::without_interrupts(|| {
-        WRITER
-            .lock()
+        SCREENS.lock()[vt]
             .write_fmt(args)
             .expect("Failed to write to VGA");
     });
@@ -0,0 +1,3 @@
+pub(crate) mod channel;
+pub(crate) mod pipe;
+pub(crate) mod shm;
@@ -0,0 +1,179 @@
+use crate::kernel::{
+    resource::{Description, PollFlag},
+    scheduler,
+};
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+use spin::Mutex;
+
+/// Largest message, in bytes.
+pub(crate) const MAX_MESSAGE_SIZE: usize = 4096;
+
+/// Most resources a message can carry.
+pub(crate) const MAX_MESSAGE_RESOURCES: usize = 8;
+
+// messages waiting on one end before senders have to wait
+const MAX_MESSAGES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChannelError {
+    PeerClosed,
+    TooLarge, // the next message doesn't fit in what the receiver offers
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Message {
+    pub(crate) data: Vec<u8>,
+    pub(crate) resources: Vec<Description>, // moved out of the handles of the sender
+}
+
+#[derive(Debug)]
+struct Channel {
+    queues: [VecDeque<Message>; 2], // messages waiting to be received by each end
+    handles: [usize; 2],            // descriptions open on each end
+}
+
+/// One end of a channel, the resource behind each of the handles created by the `CHANNEL`
+/// syscall. Messages sent on one end are received on the other, in order.
+///
+/// As with pipes, a new description of an end has to be registered with `share`, and `close`
+/// called when it goes away.
+#[derive(Debug, Clone)]
+pub(crate) struct ChannelEnd {
+    chan: Arc<Mutex<Channel>>,
+    side: usize,
+}
+
+/// Creates a channel, returning both of its ends.
+pub(crate) fn channel() -> (ChannelEnd, ChannelEnd) {
+    let chan = Arc::new(Mutex::new(Channel {
+        queues: [VecDeque::new(), VecDeque::new()],
+        handles: [1, 1],
+    }));
+
+    let end = |side| ChannelEnd {
+        chan: chan.clone(),
+        side,
+    };
+
+    (end(0), end(1))
+}
+
+impl ChannelEnd {
+    const fn peer(&self) -> usize {
+        1 - self.side
+    }
+
+    /// Whether both are the same end of the same channel.
+    pub(crate) fn is(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.chan, &other.chan) && self.side == other.side
+    }
+
+    /// Whether both are ends of the same channel.
+    pub(crate) fn is_peer(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.chan, &other.chan) && self.side != other.side
+    }
+
+    /// What the end can do without waiting.
+    pub(crate) fn poll(&self) -> PollFlag {
+        let chan = self.chan.lock();
+        let peer_open = chan.handles[self.peer()] > 0;
+        let mut flags = PollFlag::empty();
+
+        flags.set(PollFlag::READ, !chan.queues[self.side].is_empty());
+        flags.set(
+            PollFlag::WRITE,
+            peer_open && chan.queues[self.peer()].len() < MAX_MESSAGES,
+        );
+        flags.set(PollFlag::HANGUP, !peer_open);
+
+        flags
+    }
+
+    /// Registers one more description of this end.
+    pub(crate) fn share(&self) {
+        self.chan.lock().handles[self.side] += 1;
+    }
+
+    /// Unregisters a description of this end, the messages nobody can receive anymore are
+    /// dropped along with their resources.
+    pub(crate) fn close(&self) {
+        let dropped = {
+            let mut chan = self.chan.lock();
+            let handles = &mut chan.handles[self.side];
+            *handles = handles.saturating_sub(1);
+
+            if *handles > 0 {
+                return;
+            }
+
+            chan.queues[self.side].drain(..).collect::<Vec<_>>()
+        };
+
+        // closed once the channel is unlocked since they can be channels as well
+        for msg in dropped {
+            for desc in msg.resources {
+                desc.close();
+            }
+        }
+    }
+
+    /// Queues `msg` for the other end, waiting while too many messages are queued already.
+    /// The message is given back if the other end is closed.
+    pub(crate) fn send(&self, msg: Message) -> Result<(), (ChannelError, Message)> {
+        if msg.data.len() > MAX_MESSAGE_SIZE || msg.resources.len() > MAX_MESSAGE_RESOURCES {
+            return Err((ChannelError::TooLarge, msg));
+        }
+
+        let mut msg = Some(msg);
+
+        scheduler::wait_until(|| {
+            let mut chan = self.chan.lock();
+            let peer = self.peer();
+
+            if chan.handles[peer] > 0 && chan.queues[peer].len() >= MAX_MESSAGES {
+                return false;
+            }
+
+            if chan.handles[peer] > 0 {
+                chan.queues[peer].extend(msg.take());
+            }
+
+            true
+        });
+
+        match msg {
+            Some(msg) => Err((ChannelError::PeerClosed, msg)),
+            None => Ok(()),
+        }
+    }
+
+    /// Waits for a message from the other end, which is only taken if it has at most `max_len`
+    /// bytes and `max_resources` resources. Messages sent before the other end was closed can
+    /// still be received.
+    pub(crate) fn receive(
+        &self,
+        max_len: usize,
+        max_resources: usize,
+    ) -> Result<Message, ChannelError> {
+        let mut res = Err(ChannelError::PeerClosed);
+
+        scheduler::wait_until(|| {
+            let mut chan = self.chan.lock();
+            let peer_open = chan.handles[self.peer()] > 0;
+            let queue = &mut chan.queues[self.side];
+
+            res = match queue.front() {
+                None if peer_open => return false,
+                None => Err(ChannelError::PeerClosed),
+                Some(msg) if msg.data.len() > max_len || msg.resources.len() > max_resources => {
+                    Err(ChannelError::TooLarge)
+                }
+                Some(_) => queue.pop_front().ok_or(ChannelError::PeerClosed),
+            };
+
+            true
+        });
+
+        res
+    }
+}
@@ -0,0 +1,211 @@
+use crate::kernel::{fs::FileIO, resource::PollFlag, scheduler};
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+use spin::Mutex;
+
+/// Bytes a pipe holds before its writers have to wait for a reader.
+pub(crate) const PIPE_SIZE: usize = 4096;
+
+/// A FIFO node, as the mount holding it and its address there.
+pub(crate) type FifoId = (usize, u32);
+
+type SharedPipe = Arc<Mutex<Pipe>>;
+
+// the pipes of the FIFO nodes opened so far
+static FIFOS: Mutex<BTreeMap<FifoId, SharedPipe>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug)]
+struct Pipe {
+    buf: VecDeque<u8>,
+    readers: usize, // descriptions open on each end
+    writers: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum End {
+    Read,
+    Write,
+}
+
+/// One end of a pipe, the resource behind each of the handles created by the `PIPE` syscall.
+///
+/// Open descriptions are counted on each end so readers get EOF once every writer is closed and
+/// writers fail once every reader is. A new description of an end has to be registered with
+/// `share`, and `close` called when it goes away.
+#[derive(Debug, Clone)]
+pub(crate) struct PipeEnd {
+    pipe: SharedPipe,
+    end: End,
+}
+
+impl Pipe {
+    fn new(readers: usize, writers: usize) -> SharedPipe {
+        Arc::new(Mutex::new(Self {
+            buf: VecDeque::with_capacity(PIPE_SIZE),
+            readers,
+            writers,
+        }))
+    }
+}
+
+/// Creates a pipe, returning its read end then its write end.
+pub(crate) fn pipe() -> (PipeEnd, PipeEnd) {
+    let pipe = Pipe::new(1, 1);
+
+    let reader = PipeEnd {
+        pipe: pipe.clone(),
+        end: End::Read,
+    };
+
+    let writer = PipeEnd {
+        pipe,
+        end: End::Write,
+    };
+
+    (reader, writer)
+}
+
+/// Opens the read or the write end of the pipe of the FIFO node `node`, the pipe being created
+/// the first time.
+pub(crate) fn open_fifo(node: FifoId, write: bool) -> PipeEnd {
+    let pipe = FIFOS
+        .lock()
+        .entry(node)
+        .or_insert_with(|| Pipe::new(0, 0))
+        .clone();
+
+    let end = PipeEnd {
+        pipe,
+        end: if write { End::Write } else { End::Read },
+    };
+
+    end.share();
+    end
+}
+
+/// Forgets the pipe of a removed FIFO node, only the handles still open on it can use it.
+pub(crate) fn remove_fifo(node: FifoId) {
+    FIFOS.lock().remove(&node);
+}
+
+impl PipeEnd {
+    pub(crate) fn is_reader(&self) -> bool {
+        self.end == End::Read
+    }
+
+    /// Bytes written to the pipe and not read yet.
+    pub(crate) fn len(&self) -> usize {
+        self.pipe.lock().buf.len()
+    }
+
+    /// Whether writing fails because every read end has been closed.
+    pub(crate) fn is_broken(&self) -> bool {
+        self.end == End::Write && self.pipe.lock().readers == 0
+    }
+
+    /// What the end can do without waiting, reading an end without writers returns at once.
+    pub(crate) fn poll(&self) -> PollFlag {
+        let pipe = self.pipe.lock();
+        let mut flags = PollFlag::empty();
+
+        match self.end {
+            End::Read => {
+                flags.set(PollFlag::READ, !pipe.buf.is_empty() || pipe.writers == 0);
+                flags.set(PollFlag::HANGUP, pipe.writers == 0);
+            }
+            End::Write => {
+                flags.set(
+                    PollFlag::WRITE,
+                    pipe.buf.len() < PIPE_SIZE && pipe.readers > 0,
+                );
+                flags.set(PollFlag::HANGUP, pipe.readers == 0);
+            }
+        }
+
+        flags
+    }
+
+    /// Registers one more description of this end.
+    pub(crate) fn share(&self) {
+        let mut pipe = self.pipe.lock();
+
+        match self.end {
+            End::Read => pipe.readers += 1,
+            End::Write => pipe.writers += 1,
+        }
+    }
+
+    /// Unregisters a description of this end.
+    pub(crate) fn close(&self) {
+        let mut pipe = self.pipe.lock();
+
+        match self.end {
+            End::Read => pipe.readers = pipe.readers.saturating_sub(1),
+            End::Write => pipe.writers = pipe.writers.saturating_sub(1),
+        }
+    }
+}
+
+impl FileIO for PipeEnd {
+    /// Waits for data as long as the pipe has writers, then reads what is available. Nothing is
+    /// read once the pipe is empty and its writers are gone.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        if self.end != End::Read {
+            return Err(());
+        }
+
+        let mut n = 0;
+
+        scheduler::wait_until(|| {
+            let mut pipe = self.pipe.lock();
+
+            if pipe.buf.is_empty() && pipe.writers > 0 && !buf.is_empty() {
+                return false;
+            }
+
+            n = buf.len().min(pipe.buf.len());
+
+            for (byte, b) in buf.iter_mut().zip(pipe.buf.drain(..n)) {
+                *byte = b;
+            }
+
+            true
+        });
+
+        Ok(n)
+    }
+
+    /// Writes the whole buffer, waiting for readers to make room when the pipe is full.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, ()> {
+        if self.end != End::Write {
+            return Err(());
+        }
+
+        let mut n = 0;
+        let mut broken = false;
+
+        while n < buf.len() && !broken {
+            scheduler::wait_until(|| {
+                let mut pipe = self.pipe.lock();
+
+                if pipe.readers == 0 {
+                    broken = true;
+                    return true;
+                }
+
+                let count = (PIPE_SIZE - pipe.buf.len()).min(buf.len() - n);
+                pipe.buf.extend(&buf[n..n + count]);
+                n += count;
+
+                count > 0
+            });
+        }
+
+        match broken {
+            true => Err(()),
+            false => Ok(n),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+#[cfg(target_arch = "x86_64")]
+use crate::kernel::arch::mem::{self, allocator, FRAME_SIZE};
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use spin::Mutex;
+
+/// Largest shared memory object, in bytes.
+pub(crate) const MAX_SHM_SIZE: usize = 16 << 20;
+
+/// Where processes map shared memory, up to `MAP_END`. This range has its own entry in the
+/// level 4 table of each forked process, the rest of the table being the kernel's.
+pub(crate) const MAP_START: u64 = 0x_5555_0000_0000;
+pub(crate) const MAP_END: u64 = 0x_5580_0000_0000;
+
+static NAMED: Mutex<BTreeMap<String, SharedMemory>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug)]
+struct Object {
+    name: String, // empty for objects only reachable through handles
+    size: usize,
+    frames: Vec<u64>,
+    refs: usize, // open descriptions and mappings
+}
+
+/// Memory that several processes can map at once, the resource behind the handles created by
+/// the `SHM_OPEN` syscall.
+///
+/// Its frames are allocated when it is first mapped and given back once the last handle and
+/// mapping are gone. As with pipes, a new description of the object has to be registered with
+/// `share`.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedMemory(Arc<Mutex<Object>>);
+
+/// Opens the object called `name`, creating it with `size` bytes when it doesn't exist or when
+/// `name` is empty. An existing object must be at least `size` bytes, a size of 0 only opens.
+pub(crate) fn open(name: &str, size: usize) -> Option<SharedMemory> {
+    let mut named = NAMED.lock();
+
+    if let Some(shm) = named.get(name) {
+        if shm.size() < size {
+            return None;
+        }
+
+        shm.share();
+        return Some(shm.clone());
+    }
+
+    if size == 0 || size > MAX_SHM_SIZE {
+        return None;
+    }
+
+    let shm = SharedMemory(Arc::new(Mutex::new(Object {
+        name: name.into(),
+        size,
+        frames: Vec::new(),
+        refs: 1,
+    })));
+
+    if !name.is_empty() {
+        named.insert(name.into(), shm.clone());
+    }
+
+    Some(shm)
+}
+
+impl SharedMemory {
+    pub(crate) fn name(&self) -> String {
+        self.0.lock().name.clone()
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.0.lock().size
+    }
+
+    /// Bytes taken by a mapping of the object, a whole number of pages.
+    pub(crate) fn mapped_size(&self) -> usize {
+        self.size().div_ceil(FRAME_SIZE) * FRAME_SIZE
+    }
+
+    /// Whether both refer to the same object.
+    pub(crate) fn is(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Registers one more description or mapping.
+    pub(crate) fn share(&self) {
+        self.0.lock().refs += 1;
+    }
+
+    /// Unregisters a description or a mapping, the object goes away with the last one.
+    pub(crate) fn close(&self) {
+        let name = {
+            let mut obj = self.0.lock();
+            obj.refs = obj.refs.saturating_sub(1);
+
+            if obj.refs > 0 {
+                return;
+            }
+
+            mem::free_frames(&obj.frames);
+            obj.frames.clear();
+            obj.name.clone()
+        };
+
+        if !name.is_empty() {
+            NAMED.lock().remove(&name);
+        }
+    }
+
+    /// Maps the whole object at `addr` in the page table at `table`, or the active one, where
+    /// user space can reach it.
+    pub(crate) fn map(&self, table: Option<u64>, addr: u64) -> Result<(), ()> {
+        let mut obj = self.0.lock();
+
+        if obj.frames.is_empty() {
+            let count = obj.size.div_ceil(FRAME_SIZE);
+            obj.frames = mem::alloc_frames(count).ok_or(())?;
+        }
+
+        if allocator::map(table, addr, &obj.frames).is_err() {
+            allocator::unmap(table, addr, obj.size);
+            return Err(());
+        }
+
+        obj.refs += 1;
+        Ok(())
+    }
+
+    /// Removes the mapping made by `map` at `addr` in the same page table.
+    pub(crate) fn unmap(&self, table: Option<u64>, addr: u64) {
+        allocator::unmap(table, addr, self.size());
+        self.close();
+    }
+}
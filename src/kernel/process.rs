@@ -1,15 +1,19 @@
 #[cfg(target_arch = "x86_64")]
-use super::arch::mem::allocator;
+use super::arch::mem::{self, allocator};
 
 use super::{
+    fs::{FileKind, OpenFlag},
     io::{console::Console, recoverable},
-    resource::{Device, Resource},
+    ipc::shm::{self, SharedMemory},
+    resource::{Description, Device, Resource},
     scheduler::{TaskId, TaskPriority, TaskStatus},
 };
 use alloc::{
     boxed::Box,
     collections::{BTreeMap, BTreeSet},
+    format,
     string::String,
+    vec::Vec,
 };
 use bitflags::bitflags;
 use core::sync::atomic::{AtomicU64, Ordering};
@@ -60,6 +64,10 @@ impl ThreadId {
         static COUNTER: AtomicU64 = AtomicU64::new(0);
         ThreadId(COUNTER.fetch_add(1, Ordering::Relaxed))
     }
+
+    pub(crate) const fn inner(&self) -> u64 {
+        self.0
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -88,9 +96,10 @@ pub(crate) fn current_thread() -> Thread {
 }
 
 const MAX_RESOURCE_HANDLES: usize = 64;
+const MIN_CREATED_HANDLE: usize = 4; // the first ones are the standard streams and null
 const MAX_THREADS: usize = 100;
 const MAX_PROCESSES: usize = 50;
-const MAX_PROC_SIZE: usize = 4 << 40;
+pub(crate) const MAX_PROC_SIZE: usize = 4 << 40;
 
 static CODE_ADDR: AtomicU64 = AtomicU64::new(0);
 
@@ -168,27 +177,61 @@ bitflags! {
     }
 }
 
+impl ProcessPrivileges {
+    /// Converts the `rwxrwxrwx` bits of a Unix mode into the privileges for a node of `kind`.
+    pub(crate) fn from_mode(mode: u32, kind: FileKind) -> Self {
+        // position of the read bit of each kind within a class
+        let shift = match kind {
+            FileKind::File => 0,
+            FileKind::Dir => 3,
+            FileKind::Device => 6,
+            FileKind::Link => 9,
+            FileKind::Fifo => 12, // FIFOs are given the bits of sockets
+        };
+
+        let everyone = (mode & 0o7) as u64;
+        let group = (mode >> 3 & 0o7) as u64;
+        let owner = (mode >> 6 & 0o7) as u64;
+
+        // Unix puts read first, the flags start with it as well
+        let swap = |rwx: u64| (rwx & 0b100) >> 2 | (rwx & 0b010) | (rwx & 0b001) << 2;
+        let bits =
+            swap(everyone) << shift | swap(group) << (15 + shift) | swap(owner) << (30 + shift);
+
+        Self::from_bits_truncate(bits)
+    }
+}
+
+/// The ID of a user as listed in the user database, processes start as root and keep the user
+/// of their parent.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) struct ProcessUserId(u64);
 
 impl ProcessUserId {
-    fn new() -> Self {
-        static COUNTER: AtomicU64 = AtomicU64::new(0);
-        ProcessUserId(COUNTER.fetch_add(1, Ordering::Relaxed))
+    pub(crate) const ROOT: Self = Self(0);
+
+    pub(crate) const fn new(id: u64) -> Self {
+        Self(id)
     }
 
     pub(crate) const fn inner(&self) -> u64 {
         self.0
     }
+
+    /// Root is allowed to access every node regardless of its permissions.
+    pub(crate) const fn is_root(&self) -> bool {
+        self.0 == 0
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) struct ProcessGroupId(u64);
 
 impl ProcessGroupId {
-    fn new() -> Self {
-        static COUNTER: AtomicU64 = AtomicU64::new(0);
-        ProcessGroupId(COUNTER.fetch_add(1, Ordering::Relaxed))
+    pub(crate) const ROOT: Self = Self(0);
+
+    pub(crate) const fn new(id: u64) -> Self {
+        Self(id)
     }
 
     pub(crate) const fn inner(&self) -> u64 {
@@ -203,6 +246,44 @@ pub(crate) struct ProcessSchedulingInfo {
     task_status: TaskStatus,
 }
 
+/// An entry of the handle table, the handles sharing a description are still closed on exec
+/// one by one.
+#[derive(Debug, Clone)]
+struct Handle {
+    desc: Description,
+    close_on_exec: bool,
+}
+
+impl Handle {
+    fn new(resource: Resource) -> Self {
+        Self {
+            desc: Description::new(resource, OpenFlag::empty()),
+            close_on_exec: false,
+        }
+    }
+}
+
+/// The page table a process maps shared memory in.
+///
+/// Processes aren't switched yet, so only the boot table is ever loaded: the tables of forked
+/// processes are kept up to date but never used, and the mappings reaching user space are all
+/// in the boot table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AddressSpace {
+    Boot,     // the one set up by the bootloader, for the processes made at boot
+    Empty,    // none yet, one is made with the first mapping
+    Own(u64), // physical address of its level 4 table
+}
+
+impl AddressSpace {
+    const fn table(self) -> Option<u64> {
+        match self {
+            Self::Own(table) => Some(table),
+            Self::Boot | Self::Empty => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct Process {
     id: ProcessId,
@@ -215,7 +296,9 @@ pub(crate) struct Process {
     group: ProcessGroupId,
     env: BTreeMap<String, String>,
     threads: [Option<Box<Thread>>; MAX_THREADS],
-    resource_handles: [Option<Box<Resource>>; MAX_RESOURCE_HANDLES],
+    resource_handles: [Option<Handle>; MAX_RESOURCE_HANDLES],
+    mappings: BTreeMap<u64, SharedMemory>, // shared memory mapped by the process, by address
+    addr_space: AddressSpace,
     code_addr: u64,
     stack_addr: u64,
     entry_point_addr: u64,
@@ -230,10 +313,16 @@ impl Process {
 
         let mut resource_handles = [(); MAX_RESOURCE_HANDLES].map(|_| None);
 
-        resource_handles[0] = Some(Box::new(Resource::Device(Device::Console(Console::new())))); // stdin
-        resource_handles[1] = Some(Box::new(Resource::Device(Device::Console(Console::new())))); // stdout
-        resource_handles[2] = Some(Box::new(Resource::Device(Device::Console(Console::new())))); // stderr
-        resource_handles[3] = Some(Box::new(Resource::Device(Device::Null)));
+        resource_handles[0] = Some(Handle::new(Resource::Device(Device::Console(
+            Console::new(),
+        )))); // stdin
+        resource_handles[1] = Some(Handle::new(Resource::Device(Device::Console(
+            Console::new(),
+        )))); // stdout
+        resource_handles[2] = Some(Handle::new(Resource::Device(Device::Console(
+            Console::new(),
+        )))); // stderr
+        resource_handles[3] = Some(Handle::new(Resource::Device(Device::Null)));
 
         Self {
             id,
@@ -241,27 +330,75 @@ impl Process {
             children: BTreeSet::new(),
             privileges: !ProcessPrivileges::from_bits(0b111111111111111111111111111111).unwrap(),
             dir: dir.into(),
-            user: ProcessUserId::new(),
-            group: ProcessGroupId::new(),
+            user: ProcessUserId::ROOT,
+            group: ProcessGroupId::ROOT,
             env: BTreeMap::new(),
             threads,
             resource_handles,
+            mappings: BTreeMap::new(),
+            addr_space: AddressSpace::Boot,
             code_addr: 0,
             stack_addr: 0,
             entry_point_addr: 0,
         }
     }
 
-    pub(crate) fn fork(&mut self) -> Self {
+    /// A copy of the process to become its child through `add_child`, it doesn't hold the
+    /// descriptions behind its handles before that.
+    pub(crate) fn fork(&self) -> Self {
         let mut child = self.clone();
         child.set_parent(self.id());
         child.children.clear();
-        self.children.insert(child.id);
+
+        // shared memory has to be mapped again in a page table of its own
+        child.mappings.clear();
+        child.addr_space = AddressSpace::Empty;
 
         child
     }
 
-    pub(crate) fn exit(self, code: u8) -> ExitCode {
+    /// Stores `child`, made by `fork`, in a free slot of `PROCESSES` whose PID it takes.
+    pub(crate) fn add_child(&mut self, mut child: Self) -> Result<ProcessId, ()> {
+        let mut procs = PROCESSES.write();
+
+        // the slots without a parent are free, apart from the first process
+        let slot = procs
+            .iter_mut()
+            .skip(1)
+            .find(|proc| proc.parent.is_none())
+            .ok_or(())?;
+
+        child.id = slot.id;
+
+        for thread in child.threads.iter_mut().flatten() {
+            thread.proc = child.id;
+        }
+
+        for handle in child.resource_handles.iter().flatten() {
+            handle.desc.share();
+        }
+
+        self.children.insert(child.id);
+        **slot = child;
+        Ok(slot.id)
+    }
+
+    /// Releases what the process holds, which has to be stored back in `PROCESSES` so that
+    /// nothing is released twice.
+    pub(crate) fn exit(&mut self, code: u8) -> ExitCode {
+        for (addr, shm) in core::mem::take(&mut self.mappings) {
+            shm.unmap(self.addr_space.table(), addr);
+        }
+
+        if let AddressSpace::Own(table) = self.addr_space {
+            mem::free_page_table(table, shm::MAP_START);
+            self.addr_space = AddressSpace::Empty;
+        }
+
+        for handle in 0..MAX_RESOURCE_HANDLES {
+            self.delete_handle(handle);
+        }
+
         allocator::free(self.code_addr, MAX_PROC_SIZE);
 
         ExitCode::from(code as usize)
@@ -280,16 +417,74 @@ impl Process {
         self.parent = Some(pid);
     }
 
+    pub(crate) const fn parent_id(&self) -> Option<ProcessId> {
+        self.parent
+    }
+
+    pub(crate) fn children(&self) -> impl Iterator<Item = ProcessId> + '_ {
+        self.children.iter().copied()
+    }
+
+    pub(crate) const fn privileges(&self) -> ProcessPrivileges {
+        self.privileges
+    }
+
+    pub(crate) const fn user(&self) -> ProcessUserId {
+        self.user
+    }
+
+    pub(crate) const fn group(&self) -> ProcessGroupId {
+        self.group
+    }
+
+    pub(crate) fn set_user(&mut self, user: ProcessUserId) {
+        self.user = user;
+    }
+
+    pub(crate) fn set_group(&mut self, group: ProcessGroupId) {
+        self.group = group;
+    }
+
+    pub(crate) fn threads(&self) -> impl Iterator<Item = &Thread> {
+        self.threads.iter().flatten().map(|thread| thread.as_ref())
+    }
+
+    /// The open handles along with a copy of the resource behind each of them.
+    pub(crate) fn handles(&self) -> impl Iterator<Item = (usize, Resource)> + '_ {
+        self.resource_handles
+            .iter()
+            .enumerate()
+            .filter_map(|(handle, slot)| Some((handle, slot.as_ref()?.desc.resource())))
+    }
+
+    /// A copy of the resource behind `handle`, changes to it are only kept once given back to
+    /// `update_handle`.
     pub(crate) fn handle(&self, handle: usize) -> Option<Box<Resource>> {
-        self.resource_handles[handle].clone()
+        self.description(handle)
+            .map(|desc| Box::new(desc.resource()))
+    }
+
+    pub(crate) fn description(&self, handle: usize) -> Option<Description> {
+        Some(self.resource_handles.get(handle)?.as_ref()?.desc.clone())
     }
 
     pub(crate) fn create_handle(&mut self, resource: Resource) -> Result<usize, ()> {
-        let (min, max) = (4, MAX_RESOURCE_HANDLES);
+        self.add_handle(Description::new(resource, OpenFlag::empty()), false)
+    }
+
+    /// Gives the first free handle to `desc`, which is then held by that handle.
+    pub(crate) fn add_handle(
+        &mut self,
+        desc: Description,
+        close_on_exec: bool,
+    ) -> Result<usize, ()> {
+        for handle in MIN_CREATED_HANDLE..MAX_RESOURCE_HANDLES {
+            if self.resource_handles[handle].is_none() {
+                self.resource_handles[handle] = Some(Handle {
+                    desc,
+                    close_on_exec,
+                });
 
-        for handle in min..max {
-            if self.handle(handle).is_none() {
-                self.resource_handles[handle] = Some(Box::new(resource));
                 return Ok(handle);
             }
         }
@@ -298,22 +493,125 @@ impl Process {
         Err(())
     }
 
-    pub(crate) fn update_handle(&mut self, handle: usize, updated: Resource) {
-        self.resource_handles[handle] = Some(Box::new(updated));
+    /// Stores the updated resource in the description behind `handle`, for every handle
+    /// sharing it.
+    pub(crate) fn update_handle(&self, handle: usize, updated: Resource) {
+        if let Some(desc) = self.description(handle) {
+            desc.update(updated);
+        }
+    }
+
+    /// Makes `new` a handle to the description behind `old`, closing the handle `new` was
+    /// before. The copy is not closed on exec, whatever the flag of `old`.
+    pub(crate) fn dup_handle(&mut self, old: usize, new: usize) -> Result<(), ()> {
+        let desc = self.description(old).ok_or(())?;
+
+        if new >= MAX_RESOURCE_HANDLES {
+            return Err(());
+        }
+
+        if old == new {
+            return Ok(());
+        }
+
+        desc.share();
+        self.delete_handle(new);
+        self.resource_handles[new] = Some(Handle {
+            desc,
+            close_on_exec: false,
+        });
+
+        Ok(())
+    }
+
+    pub(crate) fn close_on_exec(&self, handle: usize) -> bool {
+        let slot = self.resource_handles.get(handle);
+        matches!(slot, Some(Some(handle)) if handle.close_on_exec)
+    }
+
+    pub(crate) fn set_close_on_exec(&mut self, handle: usize, close: bool) -> Result<(), ()> {
+        let slot = self.resource_handles.get_mut(handle).ok_or(())?;
+        slot.as_mut().ok_or(())?.close_on_exec = close;
+        Ok(())
+    }
+
+    /// Closes the handles marked to be closed on exec, as running another program in the
+    /// process has to.
+    pub(crate) fn close_exec_handles(&mut self) {
+        for handle in 0..MAX_RESOURCE_HANDLES {
+            if self.close_on_exec(handle) {
+                self.delete_handle(handle);
+            }
+        }
+    }
+
+    /// Number of handles `create_handle` can still create.
+    pub(crate) fn free_handles(&self) -> usize {
+        self.resource_handles[MIN_CREATED_HANDLE..]
+            .iter()
+            .filter(|res| res.is_none())
+            .count()
+    }
+
+    /// Removes a handle without closing its description, which is moved elsewhere.
+    pub(crate) fn take_handle(&mut self, handle: usize) -> Option<Description> {
+        Some(self.resource_handles.get_mut(handle)?.take()?.desc)
     }
 
     pub(crate) fn delete_handle(&mut self, handle: usize) {
-        self.resource_handles[handle] = None;
+        if let Some(handle) = self.resource_handles.get_mut(handle).and_then(Option::take) {
+            handle.desc.close();
+        }
     }
 
-    pub(crate) fn get_env(&self, key: &str) -> String {
-        self.env[key].clone()
+    /// The shared memory mapped by the process along with its address.
+    pub(crate) fn mappings(&self) -> impl Iterator<Item = (u64, &SharedMemory)> {
+        self.mappings.iter().map(|(&addr, shm)| (addr, shm))
     }
 
-    pub(crate) fn set_env(&mut self, key: &str, value: &str) {
-        if let Some(v) = self.env.get_mut(key) {
-            *v = value.into();
+    /// Maps `shm` in the page table of the process, after what it already mapped, returning its
+    /// address.
+    pub(crate) fn map(&mut self, shm: SharedMemory) -> Result<u64, ()> {
+        let addr = match self.mappings.last_key_value() {
+            Some((&addr, last)) => addr + last.mapped_size() as u64,
+            None => shm::MAP_START,
+        };
+
+        if addr + shm.mapped_size() as u64 > shm::MAP_END {
+            return Err(());
+        }
+
+        if self.addr_space == AddressSpace::Empty {
+            let table = mem::new_page_table(shm::MAP_START).ok_or(())?;
+            self.addr_space = AddressSpace::Own(table);
         }
+
+        shm.map(self.addr_space.table(), addr)?;
+        self.mappings.insert(addr, shm);
+        Ok(addr)
+    }
+
+    /// Removes the mapping at `addr` made by `map`.
+    pub(crate) fn unmap(&mut self, addr: u64) -> Result<(), ()> {
+        let shm = self.mappings.remove(&addr).ok_or(())?;
+        shm.unmap(self.addr_space.table(), addr);
+        Ok(())
+    }
+
+    pub(crate) fn get_env(&self, key: &str) -> Option<String> {
+        self.env.get(key).cloned()
+    }
+
+    pub(crate) fn set_env(&mut self, key: &str, value: &str) {
+        self.env.insert(key.into(), value.into());
+    }
+
+    pub(crate) fn unset_env(&mut self, key: &str) {
+        self.env.remove(key);
+    }
+
+    pub(crate) fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
     }
 
     pub(crate) fn dir(&self) -> String {
@@ -332,6 +630,14 @@ impl Process {
         self.code_addr = addr;
     }
 
+    pub(crate) const fn stack_addr(&self) -> u64 {
+        self.stack_addr
+    }
+
+    pub(crate) const fn entry_point_addr(&self) -> u64 {
+        self.entry_point_addr
+    }
+
     pub(crate) fn ptr_from_addr(&self, addr: u64) -> *mut u8 {
         let code_addr = self.code_addr();
 
@@ -343,6 +649,12 @@ impl Process {
     }
 }
 
+/// A copy of the running process, changes to it are only kept once given back to
+/// `update_current_process`.
 pub(crate) fn current_process() -> Process {
     *PROCESSES.read()[0].clone()
 }
+
+pub(crate) fn update_current_process(proc: Process) {
+    *PROCESSES.write()[0] = proc;
+}
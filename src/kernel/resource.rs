@@ -1,13 +1,248 @@
 use super::{
-    fs::{Directory, File, FileIO},
-    io::console::Console,
+    fs::{
+        self, block::BlockDevice, devfs, disk::Disk, Directory, File, FileIO, FileKind, Metadata,
+        OpenFlag, SeekFlag,
+    },
+    io::{
+        console::{self, Console, TermMode},
+        keyboard, random, serial,
+        tty::{self, Termios},
+        vga,
+    },
+    ipc::{channel::ChannelEnd, pipe::PipeEnd, shm::SharedMemory},
 };
+use alloc::sync::Arc;
+use bitflags::bitflags;
+use core::str;
+use spin::Mutex;
+
+bitflags! {
+    /// What a handle is ready for, as asked to and answered by the POLL syscall.
+    pub(crate) struct PollFlag: u8 {
+        const READ = 1;
+        const WRITE = 1 << 1;
+        const HANGUP = 1 << 2; // the other end is closed, reported even when not asked for
+    }
+}
+
+/// A handle given to the POLL syscall along with what to wait for, the syscall fills in what
+/// it is ready for.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct PollEntry {
+    handle: usize,
+    interest: usize,
+    ready: usize,
+}
+
+impl PollEntry {
+    pub(crate) fn new(handle: usize, interest: PollFlag) -> Self {
+        Self {
+            handle,
+            interest: interest.bits() as usize,
+            ready: 0,
+        }
+    }
+
+    pub(crate) const fn handle(&self) -> usize {
+        self.handle
+    }
+
+    pub(crate) fn interest(&self) -> PollFlag {
+        PollFlag::from_bits_truncate(self.interest as u8) | PollFlag::HANGUP
+    }
+
+    pub(crate) fn ready(&self) -> PollFlag {
+        PollFlag::from_bits_truncate(self.ready as u8)
+    }
+
+    pub(crate) fn set_ready(&mut self, ready: PollFlag) {
+        self.ready = ready.bits() as usize;
+    }
+}
+
+/// Requests of the IOCTL syscall, each device answering the ones that make sense for it. The
+/// argument is read from the buffer given along, and the result written there.
+#[repr(usize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DeviceRequest {
+    TermSize = 1,        // the columns then the rows of the terminal, as `u16`
+    TermGetMode = 2,     // the `TermMode` of the terminal, as `u8`
+    TermSetMode = 3,     // changes the `TermMode` of the terminal, given as `u8`
+    KbdLayout = 4,       // changes the keyboard layout, given by name
+    BlockSize = 5,       // bytes in a block of the disk, as `u64`
+    BlockCount = 6,      // blocks of the disk, as `u64`
+    BlockSync = 7,       // writes back the blocks cached by the filesystems
+    TermGetAttr = 8,     // the `Termios` of the terminal, as its bytes
+    TermSetAttr = 9,     // changes the `Termios` of the terminal, given as its bytes
+    TermScrollback = 10, // changes the lines of history the terminal keeps, given as `u64`
+}
+
+impl DeviceRequest {
+    pub(crate) fn from(n: usize) -> Option<Self> {
+        use DeviceRequest::*;
+
+        match n {
+            1 => Some(TermSize),
+            2 => Some(TermGetMode),
+            3 => Some(TermSetMode),
+            4 => Some(KbdLayout),
+            5 => Some(BlockSize),
+            6 => Some(BlockCount),
+            7 => Some(BlockSync),
+            8 => Some(TermGetAttr),
+            9 => Some(TermSetAttr),
+            10 => Some(TermScrollback),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub(crate) enum Resource {
     Device(Device),
     File(File),
     Directory(Directory),
+    Pipe(PipeEnd),
+    SharedMemory(SharedMemory),
+    Channel(ChannelEnd),
+}
+
+impl Resource {
+    /// Moves the offset of files and disks, the other resources are streams and can't seek.
+    pub(crate) fn seek(&mut self, offset: isize, flag: SeekFlag) -> Result<usize, ()> {
+        match self {
+            Resource::File(f) => f.seek(offset, flag),
+            Resource::Device(Device::Disk(d)) => d.seek(offset, flag),
+            _ => Err(()),
+        }
+    }
+
+    pub(crate) fn metadata(&self) -> Option<Metadata> {
+        match self {
+            Resource::File(f) => f.metadata(),
+            Resource::Directory(dir) => dir.metadata(),
+            Resource::Device(Device::Disk(d)) => Some(Metadata::new(FileKind::Device, d.size())),
+            Resource::Device(_) => Some(Metadata::new(FileKind::Device, 0)),
+            Resource::Pipe(p) => Some(Metadata::new(FileKind::Fifo, p.len() as u64)),
+            Resource::SharedMemory(shm) => Some(Metadata::new(FileKind::File, shm.size() as u64)),
+            Resource::Channel(_) => None,
+        }
+    }
+
+    /// What the resource can do right now without waiting, files and most devices never wait.
+    pub(crate) fn poll(&self) -> PollFlag {
+        match self {
+            Resource::Pipe(p) => p.poll(),
+            Resource::Channel(c) => c.poll(),
+            Resource::Device(Device::Console(c)) if c.has_input() => {
+                PollFlag::READ | PollFlag::WRITE
+            }
+            Resource::Device(Device::Console(_)) => PollFlag::WRITE,
+            Resource::Device(Device::Serial) if serial::has_input() => {
+                PollFlag::READ | PollFlag::WRITE
+            }
+            Resource::Device(Device::Serial) => PollFlag::WRITE,
+            Resource::SharedMemory(_) => PollFlag::empty(),
+            _ => PollFlag::READ | PollFlag::WRITE,
+        }
+    }
+
+    /// Answers a request of the IOCTL syscall, only devices take any.
+    pub(crate) fn control(&mut self, req: DeviceRequest, buf: &mut [u8]) -> Result<usize, ()> {
+        match self {
+            Resource::Device(dev) => dev.control(req, buf),
+            _ => Err(()),
+        }
+    }
+
+    /// Registers one more description of the resource, for the ones that keep count of them.
+    pub(crate) fn share(&self) {
+        match self {
+            Resource::Pipe(p) => p.share(),
+            Resource::SharedMemory(shm) => shm.share(),
+            Resource::Channel(c) => c.share(),
+            Resource::Device(Device::Serial) => serial::open(),
+            _ => (),
+        }
+    }
+
+    /// Unregisters a description of the resource.
+    pub(crate) fn close(&self) {
+        match self {
+            Resource::Pipe(p) => p.close(),
+            Resource::SharedMemory(shm) => shm.close(),
+            Resource::Channel(c) => c.close(),
+            Resource::Device(Device::Serial) => serial::close(),
+            _ => (),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct OpenResource {
+    resource: Resource,
+    flags: OpenFlag,
+    handles: usize, // handles sharing the description
+}
+
+/// An open file description, the resource behind a handle along with its offset and the flags
+/// it was opened with, which are empty for the resources not opened from a path.
+///
+/// Handles duplicated from one another or inherited through fork share the description, so
+/// reading or seeking through one of them moves the offset of all of them. The resource itself
+/// is closed along with the last of these handles.
+#[derive(Debug, Clone)]
+pub(crate) struct Description(Arc<Mutex<OpenResource>>);
+
+impl Description {
+    pub(crate) fn new(resource: Resource, flags: OpenFlag) -> Self {
+        Self(Arc::new(Mutex::new(OpenResource {
+            resource,
+            flags,
+            handles: 1,
+        })))
+    }
+
+    /// A copy of the resource, changes to it are only kept once given back to `update`.
+    pub(crate) fn resource(&self) -> Resource {
+        self.0.lock().resource.clone()
+    }
+
+    pub(crate) fn update(&self, resource: Resource) {
+        self.0.lock().resource = resource;
+    }
+
+    pub(crate) fn flags(&self) -> OpenFlag {
+        self.0.lock().flags
+    }
+
+    /// Whether both are the same description.
+    pub(crate) fn is(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Registers one more handle sharing the description.
+    pub(crate) fn share(&self) {
+        self.0.lock().handles += 1;
+    }
+
+    /// Unregisters a handle, the resource is closed with the last one.
+    pub(crate) fn close(&self) {
+        let resource = {
+            let mut desc = self.0.lock();
+            desc.handles = desc.handles.saturating_sub(1);
+
+            if desc.handles > 0 {
+                return;
+            }
+
+            desc.resource.clone()
+        };
+
+        // closed once unlocked since channels close the descriptions they still hold
+        resource.close();
+    }
 }
 
 impl FileIO for Resource {
@@ -18,6 +253,9 @@ impl FileIO for Resource {
             File(f) => f.read(buf),
             Device(dev) => dev.read(buf),
             Directory(dir) => dir.read(buf),
+            Pipe(p) => p.read(buf),
+            SharedMemory(_) => Err(()), // it is mapped instead
+            Channel(_) => Err(()),      // messages have syscalls of their own
         }
     }
 
@@ -28,6 +266,8 @@ impl FileIO for Resource {
             File(f) => f.write(buf),
             Device(dev) => dev.write(buf),
             Directory(dir) => dir.write(buf),
+            Pipe(p) => p.write(buf),
+            SharedMemory(_) | Channel(_) => Err(()),
         }
     }
 }
@@ -35,22 +275,82 @@ impl FileIO for Resource {
 #[derive(Debug, Clone)]
 pub(crate) enum Device {
     Null,
+    Zero,
+    Random,
     Console(Console),
+    Serial,
+    Disk(Disk),
 }
 
 impl Device {
-    pub(crate) fn create() -> Self {
-        todo!();
+    /// Opens the device registered as `name` in devfs.
+    pub(crate) fn create(name: &str) -> Option<Self> {
+        devfs::device(name)
+    }
+
+    /// Answers `req` with its argument in `buf`, returning the number of bytes of the result
+    /// written back there. Fails when the device doesn't take the request.
+    pub(crate) fn control(&mut self, req: DeviceRequest, buf: &mut [u8]) -> Result<usize, ()> {
+        use DeviceRequest::*;
+
+        match (self, req) {
+            (Device::Console(_), TermSize) => {
+                let (cols, rows) = vga::size();
+                let [c0, c1] = (cols as u16).to_ne_bytes();
+                let [r0, r1] = (rows as u16).to_ne_bytes();
+                reply(buf, &[c0, c1, r0, r1])
+            }
+            (Device::Console(c), TermGetMode) => reply(buf, &[c.mode().bits()]),
+            (Device::Console(c), TermSetMode) => {
+                let bits = *buf.first().ok_or(())?;
+                c.set_mode(TermMode::from_bits(bits).ok_or(())?);
+                Ok(0)
+            }
+            (Device::Console(c), TermGetAttr) => reply(buf, &tty::termios(c.vt()).to_bytes()),
+            (Device::Console(c), TermSetAttr) => {
+                tty::set_termios(c.vt(), Termios::from_bytes(buf).ok_or(())?);
+                Ok(0)
+            }
+            (Device::Console(c), TermScrollback) => {
+                let lines = <[u8; 8]>::try_from(&*buf).map_err(|_| ())?;
+                vga::set_scrollback(c.vt(), u64::from_ne_bytes(lines) as usize)?;
+                Ok(0)
+            }
+            (Device::Console(_), KbdLayout) => {
+                keyboard::set_kbd_layout(str::from_utf8(buf).map_err(|_| ())?)?;
+                Ok(0)
+            }
+            (Device::Disk(d), BlockSize) => reply(buf, &(d.block_size() as u64).to_ne_bytes()),
+            (Device::Disk(d), BlockCount) => reply(buf, &(d.block_count() as u64).to_ne_bytes()),
+            (Device::Disk(_), BlockSync) => fs::sync().map(|()| 0),
+            _ => Err(()),
+        }
     }
 }
 
+// copies the result of a request to the buffer given with it, which has to be large enough
+fn reply(buf: &mut [u8], data: &[u8]) -> Result<usize, ()> {
+    buf.get_mut(..data.len()).ok_or(())?.copy_from_slice(data);
+    Ok(data.len())
+}
+
 impl FileIO for Device {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
         use Device::*;
 
         match self {
-            Null => Err(()),
+            Null => Ok(0),
+            Zero => {
+                buf.fill(0);
+                Ok(buf.len())
+            }
+            Random => {
+                random::fill(buf);
+                Ok(buf.len())
+            }
             Console(c) => c.read(buf),
+            Serial => Ok(serial::read(buf)),
+            Disk(d) => d.read(buf),
         }
     }
 
@@ -58,8 +358,10 @@ impl FileIO for Device {
         use Device::*;
 
         match self {
-            Null => Ok(0),
+            Null | Zero | Random => Ok(buf.len()),
             Console(c) => c.write(buf),
+            Serial => Ok(serial::write(buf)),
+            Disk(d) => d.write(buf),
         }
     }
 }
@@ -1,3 +1,6 @@
+#[cfg(target_arch = "x86_64")]
+use super::arch::interrupts::{halt, uptime_ms};
+
 use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, task::Wake};
 use core::{
     future::Future,
@@ -139,3 +142,29 @@ impl Scheduler {
 
 unsafe impl Send for Scheduler {}
 unsafe impl Sync for Scheduler {}
+
+/// Puts the caller to sleep until `ready` holds, which is checked again after every interrupt.
+///
+/// Processes are not preempted yet, so waiting halts the CPU until an interrupt handler makes
+/// progress possible.
+pub(crate) fn wait_until(ready: impl FnMut() -> bool) {
+    wait_until_timeout(ready, None);
+}
+
+/// Same as `wait_until`, giving up once `timeout` milliseconds have passed if there is one.
+/// Returns whether `ready` held.
+pub(crate) fn wait_until_timeout(mut ready: impl FnMut() -> bool, timeout: Option<u64>) -> bool {
+    let deadline = timeout.map(|ms| uptime_ms() + ms);
+
+    loop {
+        if ready() {
+            return true;
+        }
+
+        if matches!(deadline, Some(deadline) if uptime_ms() >= deadline) {
+            return false;
+        }
+
+        halt();
+    }
+}
@@ -1,8 +1,9 @@
 mod service;
 
 use super::{
-    fs::{self, FileIO},
+    fs::{self, FileIO, FsError, Metadata},
     process::{self, ExitCode, Process, Thread},
+    resource::{DeviceRequest, PollEntry},
 };
 use core::{arch::asm, slice, str};
 
@@ -22,6 +23,44 @@ const EXIT: usize = 0xC;
 const EXIT_GROUP: usize = 0xD;
 const REBOOT: usize = 0xE;
 const INFO: usize = 0xF;
+const FINFO: usize = 0x10;
+const READDIR: usize = 0x11;
+const MKDIR: usize = 0x12;
+const RMDIR: usize = 0x13;
+const UNLINK: usize = 0x14;
+const RENAME: usize = 0x15;
+const CHDIR: usize = 0x16;
+const GETCWD: usize = 0x17;
+const GETENV: usize = 0x18;
+const SETENV: usize = 0x19;
+const UNSETENV: usize = 0x1A;
+const LISTENV: usize = 0x1B;
+const GETUID: usize = 0x1C;
+const GETGID: usize = 0x1D;
+const SETUID: usize = 0x1E;
+const SETGID: usize = 0x1F;
+const SYMLINK: usize = 0x20;
+const READLINK: usize = 0x21;
+const LINK: usize = 0x22;
+const PIPE: usize = 0x23;
+const MKFIFO: usize = 0x24;
+const SHM_OPEN: usize = 0x25;
+const MMAP: usize = 0x26;
+const MUNMAP: usize = 0x27;
+const CHANNEL: usize = 0x28;
+const CHAN_SEND: usize = 0x29;
+const CHAN_RECV: usize = 0x2A;
+const POLL: usize = 0x2B;
+const CLOSE_ON_EXEC: usize = 0x2C;
+const IOCTL: usize = 0x2D;
+
+// failed syscalls return -1, or one of these when the failure has a more precise reason
+const PERMISSION_DENIED: isize = -2;
+const BROKEN_PIPE: isize = -3; // the other end of a pipe or a channel is closed
+                               // a control character typed on the console raised a signal
+const INTERRUPTED: isize = -4;
+const QUIT: isize = -5;
+const SUSPENDED: isize = -6;
 
 #[no_mangle]
 extern "C" fn dispatch(
@@ -71,7 +110,13 @@ extern "C" fn dispatch(
 
             service::dup(old_handle, new_handle) as usize
         }
-        SEEK => todo!(),
+        SEEK => {
+            let handle = arg0;
+            let offset = arg1 as isize;
+            let flags = arg2;
+
+            service::seek(handle, offset, flags) as usize
+        }
         PROC_SPAWN => todo!(),
         THREAD_SPAWN => todo!(),
         PROC_FORK => service::pfork() as usize,
@@ -80,7 +125,219 @@ extern "C" fn dispatch(
         THREAD_KILL => todo!(),
         EXIT => service::exit(ExitCode::from(arg0)) as usize,
         REBOOT => service::reboot(),
-        INFO => todo!(),
+        INFO => {
+            let ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let len = arg1;
+            let path = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(ptr, len)) };
+            let info = unsafe { &mut *(calling_proc.ptr_from_addr(arg2 as u64) as *mut Metadata) };
+
+            service::info(path, info) as usize
+        }
+        FINFO => {
+            let handle = arg0;
+            let info = unsafe { &mut *(calling_proc.ptr_from_addr(arg1 as u64) as *mut Metadata) };
+
+            service::finfo(handle, info) as usize
+        }
+        READDIR => {
+            let handle = arg0;
+            let ptr = calling_proc.ptr_from_addr(arg1 as u64);
+            let len = arg2;
+            let buf = unsafe { slice::from_raw_parts_mut(ptr, len) };
+
+            service::readdir(handle, buf) as usize
+        }
+        MKDIR => {
+            let ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let len = arg1;
+            let path = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(ptr, len)) };
+
+            service::mkdir(path) as usize
+        }
+        RMDIR => {
+            let ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let len = arg1;
+            let path = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(ptr, len)) };
+
+            service::rmdir(path) as usize
+        }
+        UNLINK => {
+            let ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let len = arg1;
+            let path = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(ptr, len)) };
+
+            service::unlink(path) as usize
+        }
+        RENAME => {
+            let old_ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let old_len = arg1;
+            let new_ptr = calling_proc.ptr_from_addr(arg2 as u64);
+            let new_len = arg3;
+            let old = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(old_ptr, old_len)) };
+            let new = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(new_ptr, new_len)) };
+
+            service::rename(old, new) as usize
+        }
+        CHDIR => {
+            let ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let len = arg1;
+            let path = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(ptr, len)) };
+
+            service::chdir(path) as usize
+        }
+        GETCWD => {
+            let ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let len = arg1;
+            let buf = unsafe { slice::from_raw_parts_mut(ptr, len) };
+
+            service::getcwd(buf) as usize
+        }
+        GETENV => {
+            let key_ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let key_len = arg1;
+            let ptr = calling_proc.ptr_from_addr(arg2 as u64);
+            let len = arg3;
+            let key = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(key_ptr, key_len)) };
+            let buf = unsafe { slice::from_raw_parts_mut(ptr, len) };
+
+            service::getenv(key, buf) as usize
+        }
+        SETENV => {
+            let key_ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let key_len = arg1;
+            let value_ptr = calling_proc.ptr_from_addr(arg2 as u64);
+            let value_len = arg3;
+            let key = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(key_ptr, key_len)) };
+            let value =
+                unsafe { str::from_utf8_unchecked(slice::from_raw_parts(value_ptr, value_len)) };
+
+            service::setenv(key, value) as usize
+        }
+        UNSETENV => {
+            let ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let len = arg1;
+            let key = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(ptr, len)) };
+
+            service::unsetenv(key) as usize
+        }
+        LISTENV => {
+            let ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let len = arg1;
+            let buf = unsafe { slice::from_raw_parts_mut(ptr, len) };
+
+            service::listenv(buf) as usize
+        }
+        GETUID => service::getuid() as usize,
+        GETGID => service::getgid() as usize,
+        SETUID => service::setuid(arg0) as usize,
+        SETGID => service::setgid(arg0) as usize,
+        SYMLINK => {
+            let target_ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let target_len = arg1;
+            let ptr = calling_proc.ptr_from_addr(arg2 as u64);
+            let len = arg3;
+            let target =
+                unsafe { str::from_utf8_unchecked(slice::from_raw_parts(target_ptr, target_len)) };
+            let path = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(ptr, len)) };
+
+            service::symlink(target, path) as usize
+        }
+        READLINK => {
+            let path_ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let path_len = arg1;
+            let ptr = calling_proc.ptr_from_addr(arg2 as u64);
+            let len = arg3;
+            let path =
+                unsafe { str::from_utf8_unchecked(slice::from_raw_parts(path_ptr, path_len)) };
+            let buf = unsafe { slice::from_raw_parts_mut(ptr, len) };
+
+            service::readlink(path, buf) as usize
+        }
+        LINK => {
+            let old_ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let old_len = arg1;
+            let new_ptr = calling_proc.ptr_from_addr(arg2 as u64);
+            let new_len = arg3;
+            let old = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(old_ptr, old_len)) };
+            let new = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(new_ptr, new_len)) };
+
+            service::link(old, new) as usize
+        }
+        PIPE => {
+            let handles =
+                unsafe { &mut *(calling_proc.ptr_from_addr(arg0 as u64) as *mut [usize; 2]) };
+
+            service::pipe(handles) as usize
+        }
+        MKFIFO => {
+            let ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let len = arg1;
+            let path = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(ptr, len)) };
+
+            service::mkfifo(path) as usize
+        }
+        SHM_OPEN => {
+            let ptr = calling_proc.ptr_from_addr(arg0 as u64);
+            let len = arg1;
+            let size = arg2;
+            let name = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(ptr, len)) };
+
+            service::shm_open(name, size) as usize
+        }
+        MMAP => service::mmap(arg0) as usize,
+        MUNMAP => service::munmap(arg0 as u64) as usize,
+        CHANNEL => {
+            let handles =
+                unsafe { &mut *(calling_proc.ptr_from_addr(arg0 as u64) as *mut [usize; 2]) };
+
+            service::channel(handles) as usize
+        }
+        CHAN_SEND => {
+            let handle = arg0;
+            let ptr = calling_proc.ptr_from_addr(arg1 as u64);
+            let len = arg2;
+            let handles_ptr = calling_proc.ptr_from_addr(arg3 as u64) as *const usize;
+            let handles_len = arg4;
+            let buf = unsafe { slice::from_raw_parts(ptr, len) };
+            let handles = unsafe { slice::from_raw_parts(handles_ptr, handles_len) };
+
+            service::chan_send(handle, buf, handles) as usize
+        }
+        CHAN_RECV => {
+            let handle = arg0;
+            let ptr = calling_proc.ptr_from_addr(arg1 as u64);
+            let len = arg2;
+            let handles_ptr = calling_proc.ptr_from_addr(arg3 as u64) as *mut usize;
+            let handles_len = arg4;
+            let count = unsafe { &mut *(calling_proc.ptr_from_addr(arg5 as u64) as *mut usize) };
+            let buf = unsafe { slice::from_raw_parts_mut(ptr, len) };
+            let handles = unsafe { slice::from_raw_parts_mut(handles_ptr, handles_len) };
+
+            service::chan_recv(handle, buf, handles, count) as usize
+        }
+        POLL => {
+            let ptr = calling_proc.ptr_from_addr(arg0 as u64) as *mut PollEntry;
+            let len = arg1;
+            let timeout = arg2;
+            let entries = unsafe { slice::from_raw_parts_mut(ptr, len) };
+
+            service::poll(entries, timeout) as usize
+        }
+        CLOSE_ON_EXEC => {
+            let handle = arg0;
+            let close = arg1 != 0;
+
+            service::close_on_exec(handle, close) as usize
+        }
+        IOCTL => {
+            let handle = arg0;
+            let req = arg1;
+            let ptr = calling_proc.ptr_from_addr(arg2 as u64);
+            let len = arg3;
+            let buf = unsafe { slice::from_raw_parts_mut(ptr, len) };
+
+            service::ioctl(handle, req, buf) as usize
+        }
         _ => unimplemented!("Invalid syscall ID"),
     }
 }
@@ -95,30 +352,23 @@ pub(super) fn read(handle: usize, buf: &mut [u8]) -> Option<usize> {
     }
 }
 
-pub(super) fn write(handle: usize, buf: &[u8]) -> Option<usize> {
+pub(super) fn write(handle: usize, buf: &[u8]) -> Result<usize, FsError> {
     let res = syscall3(WRITE, handle, buf.as_ptr() as usize, buf.len()) as isize;
 
-    if res >= 0 {
-        Some(res as usize)
-    } else {
-        None
-    }
+    fs_result(res)
 }
 
-pub(super) fn open(path: &str, flags: usize) -> Option<usize> {
+pub(super) fn open(path: &str, flags: usize) -> Result<usize, FsError> {
     let res = syscall3(OPEN, path.as_ptr() as usize, path.len(), flags) as isize;
 
-    if res >= 0 {
-        Some(res as usize)
-    } else {
-        None
-    }
+    fs_result(res)
 }
 
 pub fn close(handle: usize) {
     syscall1(CLOSE, handle);
 }
 
+/// Makes `new_handle` a copy of `old_handle` sharing its offset, closing what `new_handle` was.
 pub(super) fn dup(old_handle: usize, new_handle: usize) -> Option<usize> {
     let res = syscall2(DUP, old_handle, new_handle) as isize;
 
@@ -129,8 +379,8 @@ pub(super) fn dup(old_handle: usize, new_handle: usize) -> Option<usize> {
     }
 }
 
-pub(super) fn seek(handle: usize, offset: usize, flags: usize) -> Option<usize> {
-    let res = syscall3(SEEK, handle, offset, flags) as isize;
+pub(super) fn seek(handle: usize, offset: isize, flags: usize) -> Option<usize> {
+    let res = syscall3(SEEK, handle, offset as usize, flags) as isize;
 
     if res >= 0 {
         Some(res as usize)
@@ -175,8 +425,355 @@ pub(super) fn reboot() {
     syscall0(REBOOT);
 }
 
-pub(super) fn info(path: &str) {
-    todo!();
+pub(super) fn info(path: &str) -> Option<Metadata> {
+    let mut info = Metadata::default();
+    let res = syscall3(
+        INFO,
+        path.as_ptr() as usize,
+        path.len(),
+        &mut info as *mut Metadata as usize,
+    ) as isize;
+
+    if res >= 0 {
+        Some(info)
+    } else {
+        None
+    }
+}
+
+pub(super) fn finfo(handle: usize) -> Option<Metadata> {
+    let mut info = Metadata::default();
+    let res = syscall2(FINFO, handle, &mut info as *mut Metadata as usize) as isize;
+
+    if res >= 0 {
+        Some(info)
+    } else {
+        None
+    }
+}
+
+pub(super) fn readdir(handle: usize, buf: &mut [u8]) -> Option<usize> {
+    let res = syscall3(READDIR, handle, buf.as_ptr() as usize, buf.len()) as isize;
+
+    if res >= 0 {
+        Some(res as usize)
+    } else {
+        None
+    }
+}
+
+pub(super) fn mkdir(path: &str) -> Result<(), FsError> {
+    let res = syscall2(MKDIR, path.as_ptr() as usize, path.len()) as isize;
+
+    fs_result(res).map(|_| ())
+}
+
+pub(super) fn rmdir(path: &str) -> Result<(), FsError> {
+    let res = syscall2(RMDIR, path.as_ptr() as usize, path.len()) as isize;
+
+    fs_result(res).map(|_| ())
+}
+
+pub(super) fn unlink(path: &str) -> Result<(), FsError> {
+    let res = syscall2(UNLINK, path.as_ptr() as usize, path.len()) as isize;
+
+    fs_result(res).map(|_| ())
+}
+
+pub(super) fn rename(old: &str, new: &str) -> Result<(), FsError> {
+    let res = syscall4(
+        RENAME,
+        old.as_ptr() as usize,
+        old.len(),
+        new.as_ptr() as usize,
+        new.len(),
+    ) as isize;
+
+    fs_result(res).map(|_| ())
+}
+
+pub(super) fn chdir(path: &str) -> Result<(), FsError> {
+    let res = syscall2(CHDIR, path.as_ptr() as usize, path.len()) as isize;
+
+    fs_result(res).map(|_| ())
+}
+
+fn fs_result(res: isize) -> Result<usize, FsError> {
+    match res {
+        PERMISSION_DENIED => Err(FsError::PermissionDenied),
+        BROKEN_PIPE => Err(FsError::BrokenPipe),
+        res if res < 0 => Err(FsError::Failed),
+        res => Ok(res as usize),
+    }
+}
+
+pub(super) fn getcwd(buf: &mut [u8]) -> Option<usize> {
+    let res = syscall2(GETCWD, buf.as_ptr() as usize, buf.len()) as isize;
+
+    if res >= 0 {
+        Some(res as usize)
+    } else {
+        None
+    }
+}
+
+pub(super) fn getenv(key: &str, buf: &mut [u8]) -> Option<usize> {
+    let res = syscall4(
+        GETENV,
+        key.as_ptr() as usize,
+        key.len(),
+        buf.as_ptr() as usize,
+        buf.len(),
+    ) as isize;
+
+    if res >= 0 {
+        Some(res as usize)
+    } else {
+        None
+    }
+}
+
+pub(super) fn setenv(key: &str, value: &str) -> Option<()> {
+    let res = syscall4(
+        SETENV,
+        key.as_ptr() as usize,
+        key.len(),
+        value.as_ptr() as usize,
+        value.len(),
+    ) as isize;
+
+    if res >= 0 {
+        Some(())
+    } else {
+        None
+    }
+}
+
+pub(super) fn unsetenv(key: &str) -> Option<()> {
+    let res = syscall2(UNSETENV, key.as_ptr() as usize, key.len()) as isize;
+
+    if res >= 0 {
+        Some(())
+    } else {
+        None
+    }
+}
+
+pub(super) fn listenv(buf: &mut [u8]) -> Option<usize> {
+    let res = syscall2(LISTENV, buf.as_ptr() as usize, buf.len()) as isize;
+
+    if res >= 0 {
+        Some(res as usize)
+    } else {
+        None
+    }
+}
+
+pub(super) fn getuid() -> usize {
+    syscall0(GETUID)
+}
+
+pub(super) fn getgid() -> usize {
+    syscall0(GETGID)
+}
+
+pub(super) fn setuid(uid: usize) -> Option<()> {
+    let res = syscall1(SETUID, uid) as isize;
+
+    if res >= 0 {
+        Some(())
+    } else {
+        None
+    }
+}
+
+pub(super) fn setgid(gid: usize) -> Option<()> {
+    let res = syscall1(SETGID, gid) as isize;
+
+    if res >= 0 {
+        Some(())
+    } else {
+        None
+    }
+}
+
+pub(super) fn symlink(target: &str, path: &str) -> Result<(), FsError> {
+    let res = syscall4(
+        SYMLINK,
+        target.as_ptr() as usize,
+        target.len(),
+        path.as_ptr() as usize,
+        path.len(),
+    ) as isize;
+
+    fs_result(res).map(|_| ())
+}
+
+pub(super) fn readlink(path: &str, buf: &mut [u8]) -> Result<usize, FsError> {
+    let res = syscall4(
+        READLINK,
+        path.as_ptr() as usize,
+        path.len(),
+        buf.as_ptr() as usize,
+        buf.len(),
+    ) as isize;
+
+    fs_result(res)
+}
+
+pub(super) fn link(old: &str, new: &str) -> Result<(), FsError> {
+    let res = syscall4(
+        LINK,
+        old.as_ptr() as usize,
+        old.len(),
+        new.as_ptr() as usize,
+        new.len(),
+    ) as isize;
+
+    fs_result(res).map(|_| ())
+}
+
+/// Creates a pipe, returning the handle of its read end then the one of its write end.
+pub(super) fn pipe() -> Option<(usize, usize)> {
+    let mut handles = [0; 2];
+    let res = syscall1(PIPE, handles.as_mut_ptr() as usize) as isize;
+
+    if res >= 0 {
+        Some((handles[0], handles[1]))
+    } else {
+        None
+    }
+}
+
+pub(super) fn mkfifo(path: &str) -> Result<(), FsError> {
+    let res = syscall2(MKFIFO, path.as_ptr() as usize, path.len()) as isize;
+
+    fs_result(res).map(|_| ())
+}
+
+/// Opens the shared memory object `name`, which is created with `size` bytes if it doesn't
+/// exist, or only opened when `size` is 0. Objects without a name are always created and only
+/// shared through their handle.
+pub(super) fn shm_open(name: &str, size: usize) -> Option<usize> {
+    let res = syscall3(SHM_OPEN, name.as_ptr() as usize, name.len(), size) as isize;
+
+    if res >= 0 {
+        Some(res as usize)
+    } else {
+        None
+    }
+}
+
+/// Maps the shared memory object behind `handle`, returning its address.
+pub(super) fn mmap(handle: usize) -> Option<*mut u8> {
+    let res = syscall1(MMAP, handle) as isize;
+
+    if res >= 0 {
+        Some(res as *mut u8)
+    } else {
+        None
+    }
+}
+
+pub(super) fn munmap(addr: *mut u8) -> Option<()> {
+    let res = syscall1(MUNMAP, addr as usize) as isize;
+
+    if res >= 0 {
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// Creates a channel, returning the handles of both of its ends.
+pub(super) fn channel() -> Option<(usize, usize)> {
+    let mut handles = [0; 2];
+    let res = syscall1(CHANNEL, handles.as_mut_ptr() as usize) as isize;
+
+    if res >= 0 {
+        Some((handles[0], handles[1]))
+    } else {
+        None
+    }
+}
+
+/// Sends `buf` on the channel `handle` along with the resources of `handles`, which are moved
+/// to the receiver and can't be used by the sender anymore.
+pub(super) fn chan_send(handle: usize, buf: &[u8], handles: &[usize]) -> Result<(), FsError> {
+    let res = syscall5(
+        CHAN_SEND,
+        handle,
+        buf.as_ptr() as usize,
+        buf.len(),
+        handles.as_ptr() as usize,
+        handles.len(),
+    ) as isize;
+
+    fs_result(res).map(|_| ())
+}
+
+/// Receives a message from the channel `handle`, returning its size and the number of handles
+/// to its resources written to `handles`.
+pub(super) fn chan_recv(
+    handle: usize,
+    buf: &mut [u8],
+    handles: &mut [usize],
+) -> Result<(usize, usize), FsError> {
+    let mut count = 0;
+    let res = syscall6(
+        CHAN_RECV,
+        handle,
+        buf.as_ptr() as usize,
+        buf.len(),
+        handles.as_ptr() as usize,
+        handles.len(),
+        &mut count as *mut usize as usize,
+    ) as isize;
+
+    fs_result(res).map(|len| (len, count))
+}
+
+/// Waits until one of `entries` is ready for what it asks, or for `timeout` milliseconds if
+/// there is one, returning the number of entries ready, 0 when the time ran out.
+pub(super) fn poll(entries: &mut [PollEntry], timeout: Option<usize>) -> Option<usize> {
+    let timeout = timeout.unwrap_or(usize::MAX);
+    let res = syscall3(POLL, entries.as_mut_ptr() as usize, entries.len(), timeout) as isize;
+
+    if res >= 0 {
+        Some(res as usize)
+    } else {
+        None
+    }
+}
+
+/// Marks `handle` to be closed, or kept open, when the process runs another program. Handles
+/// are kept open unless opened with `OpenFlag::CLOSE_ON_EXEC`, copies made by `dup` always are.
+pub(super) fn close_on_exec(handle: usize, close: bool) -> Option<()> {
+    let res = syscall2(CLOSE_ON_EXEC, handle, close as usize) as isize;
+
+    if res >= 0 {
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// Sends `req` to the device behind `handle`, along with `buf` holding its argument. The result
+/// is written back to `buf`, returning its length.
+pub(super) fn ioctl(handle: usize, req: DeviceRequest, buf: &mut [u8]) -> Option<usize> {
+    let res = syscall4(
+        IOCTL,
+        handle,
+        req as usize,
+        buf.as_mut_ptr() as usize,
+        buf.len(),
+    ) as isize;
+
+    if res >= 0 {
+        Some(res as usize)
+    } else {
+        None
+    }
 }
 
 macro syscall_fns($(fn $name:ident($id:ident $(,$arg0:ident $(,$arg1:ident $(,$arg2:ident $(,$arg3:ident $(,$arg4:ident $(,$arg5:ident)?)?)?)?)?)?) -> usize;)*) {
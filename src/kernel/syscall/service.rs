@@ -1,8 +1,16 @@
-use core::arch::asm;
+use alloc::{string::String, vec::Vec};
+use core::{arch::asm, fmt::Write};
 
 use crate::kernel::{
-    fs::FileIO,
-    process::{self, ExitCode},
+    fs::{self, Access, FileIO, FsError, Metadata, OpenFlag, SeekFlag},
+    io::tty::Signal,
+    ipc::{
+        channel::{self, ChannelError, Message},
+        pipe, shm,
+    },
+    process::{self, ExitCode, ProcessGroupId, ProcessUserId},
+    resource::{Description, Device, DeviceRequest, PollEntry, Resource},
+    scheduler,
 };
 
 pub(super) fn read(handle: usize, buf: &mut [u8]) -> isize {
@@ -12,53 +20,553 @@ pub(super) fn read(handle: usize, buf: &mut [u8]) -> isize {
         return -1;
     };
 
-    let Ok(bytes) = res.read(buf) else {
-        return -1;
+    // reading the console only fails when a signal is raised, which the caller is told
+    let bytes = match res.as_mut() {
+        Resource::Device(Device::Console(console)) => match console.read_input(buf) {
+            Ok(bytes) => bytes,
+            Err(signal) => return signal_error(signal),
+        },
+        res => match res.read(buf) {
+            Ok(bytes) => bytes,
+            Err(()) => return -1,
+        },
     };
 
     calling_proc.update_handle(handle, *res);
+    process::update_current_process(calling_proc);
     bytes as isize
 }
 
 pub(super) fn write(handle: usize, buf: &[u8]) -> isize {
     let mut calling_proc = process::current_process();
 
-    let Some(mut res) = calling_proc.handle(handle) else {
+    let Some(desc) = calling_proc.description(handle) else {
         return -1;
     };
 
+    let mut res = desc.resource();
+
+    // every write of a description opened to append goes to the end, whoever else wrote since,
+    // streams have no end to move to
+    if desc.flags().contains(OpenFlag::APPEND) {
+        res.seek(0, SeekFlag::END).ok();
+    }
+
     let Ok(bytes) = res.write(buf) else {
-        return -1;
+        return match res {
+            Resource::Pipe(p) if p.is_broken() => super::BROKEN_PIPE,
+            _ => -1,
+        };
     };
 
-    calling_proc.update_handle(handle, *res);
+    desc.update(res);
+    process::update_current_process(calling_proc);
     bytes as isize
 }
 
 pub(super) fn open(path: &str, flags: usize) -> isize {
-    todo!();
+    let mut calling_proc = process::current_process();
+
+    let Some(flags) = OpenFlag::from_bits(flags as u8) else {
+        return -1;
+    };
+
+    // the flag belongs to the handle rather than to the description
+    let close_on_exec = flags.contains(OpenFlag::CLOSE_ON_EXEC);
+    let flags = flags - OpenFlag::CLOSE_ON_EXEC;
+
+    let res = match fs::open(path, flags.bits() as usize) {
+        Ok(res) => res,
+        Err(err) => return fs_error(err),
+    };
+
+    let desc = Description::new(res, flags);
+
+    let Ok(handle) = calling_proc.add_handle(desc.clone(), close_on_exec) else {
+        desc.close();
+        return -1;
+    };
+
+    process::update_current_process(calling_proc);
+    handle as isize
 }
 
 pub(super) fn close(handle: usize) {
     let mut calling_proc = process::current_process();
+
+    if let Some(res) = calling_proc.handle(handle) {
+        if let Resource::File(_) = *res {
+            fs::sync().ok();
+        }
+    }
+
     calling_proc.delete_handle(handle);
+    process::update_current_process(calling_proc);
 }
 
 pub(super) fn dup(old_handle: usize, new_handle: usize) -> isize {
     let mut calling_proc = process::current_process();
 
-    let Some(handle) = calling_proc.handle(old_handle) else {
+    // both handles share the offset from now on, whatever `new_handle` pointed to is closed
+    if calling_proc.dup_handle(old_handle, new_handle).is_err() {
         return -1;
-    };
+    }
 
-    calling_proc.update_handle(new_handle, *handle);
+    process::update_current_process(calling_proc);
     new_handle as isize
 }
 
-pub(super) fn seek(handle: usize, offset: usize, flags: usize) -> isize {
-    todo!();
+/// Sends the request `req` to the device behind `handle`, `buf` holding its argument and
+/// receiving its result. Returns the length of the result.
+pub(super) fn ioctl(handle: usize, req: usize, buf: &mut [u8]) -> isize {
+    let calling_proc = process::current_process();
+
+    let Some(req) = DeviceRequest::from(req) else {
+        return -1;
+    };
+
+    let Some(mut res) = calling_proc.handle(handle) else {
+        return -1;
+    };
+
+    match res.control(req, buf) {
+        Ok(n) => n as isize,
+        Err(()) => -1,
+    }
+}
+
+pub(super) fn close_on_exec(handle: usize, close: bool) -> isize {
+    let mut calling_proc = process::current_process();
+
+    if calling_proc.set_close_on_exec(handle, close).is_err() {
+        return -1;
+    }
+
+    process::update_current_process(calling_proc);
+    0
+}
+
+pub(super) fn seek(handle: usize, offset: isize, flags: usize) -> isize {
+    let mut calling_proc = process::current_process();
+
+    let Some(flag) = SeekFlag::from_bits(flags as u8) else {
+        return -1;
+    };
+
+    let Some(mut res) = calling_proc.handle(handle) else {
+        return -1;
+    };
+
+    let Ok(offset) = res.seek(offset, flag) else {
+        return -1;
+    };
+
+    calling_proc.update_handle(handle, *res);
+    process::update_current_process(calling_proc);
+    offset as isize
+}
+
+pub(super) fn readdir(handle: usize, buf: &mut [u8]) -> isize {
+    let mut calling_proc = process::current_process();
+
+    let Some(mut res) = calling_proc.handle(handle) else {
+        return -1;
+    };
+
+    let Resource::Directory(dir) = res.as_mut() else {
+        return -1;
+    };
+
+    let Ok(bytes) = dir.read(buf) else {
+        return -1;
+    };
+
+    calling_proc.update_handle(handle, *res);
+    process::update_current_process(calling_proc);
+    bytes as isize
+}
+
+pub(super) fn mkdir(path: &str) -> isize {
+    match fs::mkdir(path) {
+        Ok(()) => 0,
+        Err(err) => fs_error(err),
+    }
+}
+
+pub(super) fn rmdir(path: &str) -> isize {
+    match fs::rmdir(path) {
+        Ok(()) => 0,
+        Err(err) => fs_error(err),
+    }
+}
+
+pub(super) fn unlink(path: &str) -> isize {
+    match fs::unlink(path) {
+        Ok(()) => 0,
+        Err(err) => fs_error(err),
+    }
+}
+
+pub(super) fn rename(old: &str, new: &str) -> isize {
+    match fs::rename(old, new) {
+        Ok(()) => 0,
+        Err(err) => fs_error(err),
+    }
+}
+
+pub(super) fn mkfifo(path: &str) -> isize {
+    match fs::mkfifo(path) {
+        Ok(()) => 0,
+        Err(err) => fs_error(err),
+    }
+}
+
+pub(super) fn symlink(target: &str, path: &str) -> isize {
+    match fs::symlink(target, path) {
+        Ok(()) => 0,
+        Err(err) => fs_error(err),
+    }
+}
+
+pub(super) fn readlink(path: &str, buf: &mut [u8]) -> isize {
+    match fs::readlink(path) {
+        Ok(target) => copy_str(&target, buf),
+        Err(err) => fs_error(err),
+    }
+}
+
+pub(super) fn link(old: &str, new: &str) -> isize {
+    match fs::link(old, new) {
+        Ok(()) => 0,
+        Err(err) => fs_error(err),
+    }
+}
+
+pub(super) fn chdir(path: &str) -> isize {
+    let mut calling_proc = process::current_process();
+    let path = fs::realpath(path);
+
+    if fs::Directory::open(&path).is_none() {
+        return -1;
+    }
+
+    if let Err(err) = fs::check_access(&path, Access::Exec) {
+        return fs_error(err);
+    }
+
+    calling_proc.set_dir(&path);
+    process::update_current_process(calling_proc);
+    0
+}
+
+pub(super) fn getcwd(buf: &mut [u8]) -> isize {
+    let calling_proc = process::current_process();
+    copy_str(&calling_proc.dir(), buf)
+}
+
+pub(super) fn getenv(key: &str, buf: &mut [u8]) -> isize {
+    let calling_proc = process::current_process();
+
+    let Some(value) = calling_proc.get_env(key) else {
+        return -1;
+    };
+
+    copy_str(&value, buf)
+}
+
+pub(super) fn setenv(key: &str, value: &str) -> isize {
+    let mut calling_proc = process::current_process();
+
+    if key.is_empty() || key.contains('=') || key.contains('\0') || value.contains('\0') {
+        return -1;
+    }
+
+    calling_proc.set_env(key, value);
+    process::update_current_process(calling_proc);
+    0
+}
+
+pub(super) fn unsetenv(key: &str) -> isize {
+    let mut calling_proc = process::current_process();
+
+    calling_proc.unset_env(key);
+    process::update_current_process(calling_proc);
+    0
+}
+
+/// Writes every variable as `KEY=VALUE` followed by a null byte.
+pub(super) fn listenv(buf: &mut [u8]) -> isize {
+    let calling_proc = process::current_process();
+    let mut list = String::new();
+
+    for (key, value) in calling_proc.env() {
+        write!(list, "{}={}\0", key, value).ok();
+    }
+
+    copy_str(&list, buf)
+}
+
+pub(super) fn getuid() -> isize {
+    process::current_process().user().inner() as isize
+}
+
+pub(super) fn getgid() -> isize {
+    process::current_process().group().inner() as isize
+}
+
+/// Changes the user of the process, which only root can do unless it keeps the same user.
+pub(super) fn setuid(uid: usize) -> isize {
+    let mut calling_proc = process::current_process();
+    let user = ProcessUserId::new(uid as u64);
+
+    if !calling_proc.user().is_root() && calling_proc.user() != user {
+        return super::PERMISSION_DENIED;
+    }
+
+    calling_proc.set_user(user);
+    process::update_current_process(calling_proc);
+    0
+}
+
+/// Changes the group of the process, with the same restriction as `setuid`.
+pub(super) fn setgid(gid: usize) -> isize {
+    let mut calling_proc = process::current_process();
+    let group = ProcessGroupId::new(gid as u64);
+
+    if !calling_proc.user().is_root() && calling_proc.group() != group {
+        return super::PERMISSION_DENIED;
+    }
+
+    calling_proc.set_group(group);
+    process::update_current_process(calling_proc);
+    0
+}
+
+/// Creates a pipe, storing the handle of its read end then the one of its write end.
+pub(super) fn pipe(handles: &mut [usize; 2]) -> isize {
+    let mut calling_proc = process::current_process();
+    let (reader, writer) = pipe::pipe();
+
+    let (Ok(read_handle), Ok(write_handle)) = (
+        calling_proc.create_handle(Resource::Pipe(reader)),
+        calling_proc.create_handle(Resource::Pipe(writer)),
+    ) else {
+        return -1;
+    };
+
+    *handles = [read_handle, write_handle];
+    process::update_current_process(calling_proc);
+    0
+}
+
+pub(super) fn shm_open(name: &str, size: usize) -> isize {
+    let mut calling_proc = process::current_process();
+
+    let Some(shm) = shm::open(name, size) else {
+        return -1;
+    };
+
+    let Ok(handle) = calling_proc.create_handle(Resource::SharedMemory(shm.clone())) else {
+        shm.close();
+        return -1;
+    };
+
+    process::update_current_process(calling_proc);
+    handle as isize
+}
+
+/// Maps the shared memory behind `handle` in the process, the mapping outlives the handle.
+pub(super) fn mmap(handle: usize) -> isize {
+    let mut calling_proc = process::current_process();
+
+    let Some(Resource::SharedMemory(shm)) = calling_proc.handle(handle).map(|res| *res) else {
+        return -1;
+    };
+
+    // the page table made for a first mapping is kept even when it fails
+    let res = calling_proc.map(shm);
+    process::update_current_process(calling_proc);
+
+    match res {
+        Ok(addr) => addr as isize,
+        Err(()) => -1,
+    }
+}
+
+pub(super) fn munmap(addr: u64) -> isize {
+    let mut calling_proc = process::current_process();
+
+    if calling_proc.unmap(addr).is_err() {
+        return -1;
+    }
+
+    process::update_current_process(calling_proc);
+    0
+}
+
+/// Creates a channel, storing the handles of both of its ends.
+pub(super) fn channel(handles: &mut [usize; 2]) -> isize {
+    let mut calling_proc = process::current_process();
+    let (a, b) = channel::channel();
+
+    let (Ok(a), Ok(b)) = (
+        calling_proc.create_handle(Resource::Channel(a)),
+        calling_proc.create_handle(Resource::Channel(b)),
+    ) else {
+        return -1;
+    };
+
+    *handles = [a, b];
+    process::update_current_process(calling_proc);
+    0
+}
+
+/// Sends a message on the channel `handle`, the resources of `handles` are moved into it.
+///
+/// Neither the channel nor its other end can be sent, the message would then keep the
+/// channel open.
+pub(super) fn chan_send(handle: usize, buf: &[u8], handles: &[usize]) -> isize {
+    let mut calling_proc = process::current_process();
+
+    let Some(Resource::Channel(end)) = calling_proc.handle(handle).map(|res| *res) else {
+        return -1;
+    };
+
+    let mut resources = Vec::with_capacity(handles.len());
+
+    for (i, &h) in handles.iter().enumerate() {
+        let Some(desc) = calling_proc.description(h) else {
+            return -1;
+        };
+
+        if h == handle || handles[..i].contains(&h) {
+            return -1;
+        }
+
+        if let Resource::Channel(other) = desc.resource() {
+            if other.is_peer(&end) || other.is(&end) {
+                return -1;
+            }
+        }
+
+        resources.push(desc);
+    }
+
+    let msg = Message {
+        data: buf.to_vec(),
+        resources,
+    };
+
+    match end.send(msg) {
+        Ok(()) => {}
+        Err((ChannelError::PeerClosed, _)) => return super::BROKEN_PIPE,
+        Err(_) => return -1,
+    }
+
+    for &h in handles {
+        calling_proc.take_handle(h);
+    }
+
+    process::update_current_process(calling_proc);
+    0
+}
+
+/// Receives a message from the channel `handle`, creating a handle for each of its resources.
+pub(super) fn chan_recv(
+    handle: usize,
+    buf: &mut [u8],
+    handles: &mut [usize],
+    count: &mut usize,
+) -> isize {
+    let mut calling_proc = process::current_process();
+
+    let Some(Resource::Channel(end)) = calling_proc.handle(handle).map(|res| *res) else {
+        return -1;
+    };
+
+    let max_resources = handles.len().min(calling_proc.free_handles());
+
+    let msg = match end.receive(buf.len(), max_resources) {
+        Ok(msg) => msg,
+        Err(ChannelError::PeerClosed) => return super::BROKEN_PIPE,
+        Err(_) => return -1,
+    };
+
+    buf[..msg.data.len()].copy_from_slice(&msg.data);
+    *count = msg.resources.len();
+
+    for (slot, desc) in handles.iter_mut().zip(msg.resources) {
+        // there is room for every resource
+        *slot = calling_proc.add_handle(desc, false).unwrap_or(usize::MAX);
+    }
+
+    process::update_current_process(calling_proc);
+    msg.data.len() as isize
+}
+
+/// Waits until one of `entries` is ready for what it asks, the timeout being in milliseconds
+/// and `usize::MAX` waiting forever. Returns the number of entries ready.
+pub(super) fn poll(entries: &mut [PollEntry], timeout: usize) -> isize {
+    let calling_proc = process::current_process();
+    let mut resources = Vec::with_capacity(entries.len());
+
+    for entry in entries.iter() {
+        let Some(res) = calling_proc.handle(entry.handle()) else {
+            return -1;
+        };
+
+        resources.push(res);
+    }
+
+    let timeout = (timeout != usize::MAX).then_some(timeout as u64);
+    let mut count = 0;
+
+    scheduler::wait_until_timeout(
+        || {
+            count = 0;
+
+            for (entry, res) in entries.iter_mut().zip(&resources) {
+                let ready = res.poll() & entry.interest();
+
+                entry.set_ready(ready);
+                count += usize::from(!ready.is_empty());
+            }
+
+            count > 0
+        },
+        timeout,
+    );
+
+    count as isize
+}
+
+fn signal_error(signal: Signal) -> isize {
+    match signal {
+        Signal::Interrupt => super::INTERRUPTED,
+        Signal::Quit => super::QUIT,
+        Signal::Suspend => super::SUSPENDED,
+    }
+}
+
+fn fs_error(err: FsError) -> isize {
+    match err {
+        FsError::PermissionDenied => super::PERMISSION_DENIED,
+        FsError::BrokenPipe => super::BROKEN_PIPE,
+        FsError::Failed => -1,
+    }
+}
+
+// copies `s` at the start of `buf` if it fits
+fn copy_str(s: &str, buf: &mut [u8]) -> isize {
+    if s.len() > buf.len() {
+        return -1;
+    }
+
+    buf[..s.len()].copy_from_slice(s.as_bytes());
+    s.len() as isize
 }
 
+// TODO: lay argv and envp out on the initial stack of the program once it can be loaded
 pub(super) fn pspawn() {
     todo!();
 }
@@ -71,7 +579,12 @@ pub(super) fn pfork() -> isize {
     let mut calling_proc = process::current_process();
     let child = calling_proc.fork();
 
-    child.id().inner() as isize
+    let Ok(pid) = calling_proc.add_child(child) else {
+        return -1;
+    };
+
+    process::update_current_process(calling_proc);
+    pid.inner() as isize
 }
 
 pub(super) fn tclone() -> isize {
@@ -87,8 +600,11 @@ pub(super) fn tkill(tid: usize, signal: usize) -> isize {
 }
 
 pub(super) fn exit(code: ExitCode) -> ExitCode {
-    let calling_proc = process::current_process();
-    calling_proc.exit(code as u8)
+    let mut calling_proc = process::current_process();
+    let code = calling_proc.exit(code as u8);
+
+    process::update_current_process(calling_proc);
+    code
 }
 
 pub(super) fn exit_group(code: ExitCode) {
@@ -96,6 +612,8 @@ pub(super) fn exit_group(code: ExitCode) {
 }
 
 pub(super) fn reboot() -> usize {
+    fs::sync().ok();
+
     unsafe {
         asm!("xor rax, rax", "mov cr3, rax");
     }
@@ -103,6 +621,23 @@ pub(super) fn reboot() -> usize {
     0
 }
 
-pub(super) fn info(path: &str) -> isize {
-    todo!();
+pub(super) fn info(path: &str, info: &mut Metadata) -> isize {
+    match fs::info(path) {
+        Ok(meta) => {
+            *info = meta;
+            0
+        }
+        Err(err) => fs_error(err),
+    }
+}
+
+pub(super) fn finfo(handle: usize, info: &mut Metadata) -> isize {
+    let calling_proc = process::current_process();
+
+    let Some(meta) = calling_proc.handle(handle).and_then(|res| res.metadata()) else {
+        return -1;
+    };
+
+    *info = meta;
+    0
 }
@@ -0,0 +1,226 @@
+pub(crate) mod password;
+
+use super::{
+    fs::{self, File, FileIO},
+    io::{
+        console::{self, Console, TermMode},
+        kprint,
+    },
+    process::{self, ProcessGroupId, ProcessUserId},
+    scheduler,
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+/// The user database, one user per line as `name:password:uid:gid:home`.
+///
+/// The password field holds a hash made by `password::hash`, an empty field lets the user log
+/// in without a password until one is set.
+pub(crate) const PASSWD_PATH: &str = "/etc/passwd";
+
+// failed attempts after which `login` waits `LOCKOUT_MS` before asking again
+const MAX_ATTEMPTS: usize = 3;
+const LOCKOUT_MS: u64 = 3000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct User {
+    name: String,
+    password: String,
+    uid: ProcessUserId,
+    gid: ProcessGroupId,
+    home: String,
+}
+
+impl User {
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<_> = line.split(':').collect();
+
+        let [name, password, uid, gid, home] = fields[..] else {
+            return None;
+        };
+
+        Some(Self {
+            name: name.into(),
+            password: password.into(),
+            uid: ProcessUserId::new(uid.parse().ok()?),
+            gid: ProcessGroupId::new(gid.parse().ok()?),
+            home: home.into(),
+        })
+    }
+
+    fn line(&self) -> String {
+        format!(
+            "{}:{}:{}:{}:{}\n",
+            self.name,
+            self.password,
+            self.uid.inner(),
+            self.gid.inner(),
+            self.home
+        )
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) const fn uid(&self) -> ProcessUserId {
+        self.uid
+    }
+
+    pub(crate) const fn gid(&self) -> ProcessGroupId {
+        self.gid
+    }
+
+    pub(crate) fn home(&self) -> &str {
+        &self.home
+    }
+
+    pub(crate) fn has_password(&self) -> bool {
+        !self.password.is_empty()
+    }
+
+    pub(crate) fn check_password(&self, password: &str) -> bool {
+        !self.has_password() || password::verify(password, &self.password)
+    }
+}
+
+/// Every user of the database, lines that can't be parsed are skipped.
+pub(crate) fn users() -> Vec<User> {
+    let Some(mut file) = File::open(PASSWD_PATH) else {
+        return Vec::new();
+    };
+
+    let mut buf = vec![0; file.size() as usize];
+
+    if file.read(&mut buf).is_err() {
+        return Vec::new();
+    }
+
+    String::from_utf8_lossy(&buf)
+        .lines()
+        .filter_map(User::parse)
+        .collect()
+}
+
+pub(crate) fn find(name: &str) -> Option<User> {
+    users().into_iter().find(|user| user.name == name)
+}
+
+pub(crate) fn find_by_id(uid: ProcessUserId) -> Option<User> {
+    users().into_iter().find(|user| user.uid == uid)
+}
+
+/// The user `name` if `password` is theirs.
+pub(crate) fn authenticate(name: &str, password: &str) -> Option<User> {
+    find(name).filter(|user| user.check_password(password))
+}
+
+/// Replaces the password of `name`, the database is rewritten as a whole.
+pub(crate) fn set_password(name: &str, password: &str) -> Result<(), ()> {
+    let Some(mut file) = File::open(PASSWD_PATH) else {
+        return Err(());
+    };
+
+    let mut buf = vec![0; file.size() as usize];
+    file.read(&mut buf)?;
+
+    let mut found = false;
+    let mut content = String::new();
+
+    for line in String::from_utf8_lossy(&buf).lines() {
+        match User::parse(line) {
+            Some(mut user) if user.name == name => {
+                user.password = password::hash(password);
+                found = true;
+                content += &user.line();
+            }
+            _ => content += &format!("{}\n", line),
+        }
+    }
+
+    if !found {
+        return Err(());
+    }
+
+    file.truncate()?;
+    file.write(content.as_bytes())?;
+    fs::sync()
+}
+
+/// Makes `user` the owner of the current process, moving it to their home directory.
+pub(crate) fn switch_to(user: &User) {
+    let mut proc = process::current_process();
+
+    proc.set_user(user.uid);
+    proc.set_group(user.gid);
+    proc.set_env("USER", &user.name);
+    proc.set_env("HOME", &user.home);
+
+    if fs::Directory::open(&user.home).is_some() {
+        proc.set_dir(&user.home);
+    }
+
+    process::update_current_process(proc);
+}
+
+/// Asks for a user name and a password on the console until they match an entry of the
+/// database, then switches the current process to that user.
+///
+/// Returns `None` without a database, as there is nothing to log into: the process then stays
+/// root. Users without a password are asked to choose one.
+pub(crate) fn login() -> Option<User> {
+    File::open(PASSWD_PATH)?;
+
+    let mut failures = 0;
+
+    loop {
+        let name = prompt("login: ", true);
+        let password = prompt("password: ", false);
+
+        let Some(user) = authenticate(&name, &password) else {
+            kprint!("Login incorrect\n");
+            failures += 1;
+
+            if failures % MAX_ATTEMPTS == 0 {
+                scheduler::wait_until_timeout(|| false, Some(LOCKOUT_MS));
+            }
+
+            continue;
+        };
+
+        if !user.has_password() {
+            let password = prompt("new password: ", false);
+
+            if !password.is_empty() && set_password(&user.name, &password).is_err() {
+                kprint!("Could not set the password\n");
+            }
+        }
+
+        switch_to(&user);
+        return Some(user);
+    }
+}
+
+fn prompt(msg: &str, echo: bool) -> String {
+    kprint!("{}", msg);
+    let mut console = Console::new();
+    let mode = console.mode();
+
+    if !echo {
+        console.set_mode(mode - TermMode::ECHO);
+    }
+
+    let mut buf = [0; 256];
+    let n = console.read(&mut buf).unwrap_or(0);
+
+    if !echo {
+        console.set_mode(mode);
+        kprint!("\n");
+    }
+
+    String::from_utf8_lossy(&buf[..n]).trim_end().to_string()
+}
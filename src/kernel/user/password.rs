@@ -0,0 +1,193 @@
+use crate::kernel::io::random;
+use alloc::{format, string::String, vec::Vec};
+
+// hashes are stored as `pbkdf2-sha256$<rounds>$<salt>$<key>`, salt and key in hexadecimal
+const SCHEME: &str = "pbkdf2-sha256";
+const ROUNDS: u32 = 10000;
+const SALT_LEN: usize = 16;
+
+const BLOCK_LEN: usize = 64;
+const DIGEST_LEN: usize = 32;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+
+    for (i, word) in block.chunks(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *s = s.wrapping_add(v);
+    }
+}
+
+/// The SHA-256 digest of the concatenation of `parts`.
+pub(crate) fn sha256(parts: &[&[u8]]) -> [u8; DIGEST_LEN] {
+    let mut state = H0;
+    let mut buf = Vec::with_capacity(BLOCK_LEN * 2);
+    let mut len = 0u64;
+
+    for part in parts {
+        buf.extend_from_slice(part);
+        len += part.len() as u64;
+
+        let full = buf.len() - buf.len() % BLOCK_LEN;
+
+        for block in buf[..full].chunks(BLOCK_LEN) {
+            compress(&mut state, block);
+        }
+
+        buf.drain(..full);
+    }
+
+    // the message is padded with a 1 bit then zeros up to its length in bits
+    buf.push(0x80);
+
+    while buf.len() % BLOCK_LEN != BLOCK_LEN - 8 {
+        buf.push(0);
+    }
+
+    buf.extend_from_slice(&(len * 8).to_be_bytes());
+
+    for block in buf.chunks(BLOCK_LEN) {
+        compress(&mut state, block);
+    }
+
+    let mut res = [0; DIGEST_LEN];
+
+    for (bytes, word) in res.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+
+    res
+}
+
+fn hmac_sha256(key: &[u8], msg: &[u8]) -> [u8; DIGEST_LEN] {
+    let mut block = [0; BLOCK_LEN];
+
+    if key.len() > BLOCK_LEN {
+        block[..DIGEST_LEN].copy_from_slice(&sha256(&[key]));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let ipad = block.map(|b| b ^ 0x36);
+    let opad = block.map(|b| b ^ 0x5c);
+
+    sha256(&[&opad, &sha256(&[&ipad, msg])])
+}
+
+/// The first block of PBKDF2 with HMAC-SHA256, which is all a 32 bytes key needs.
+pub(crate) fn pbkdf2_sha256(password: &[u8], salt: &[u8], rounds: u32) -> [u8; DIGEST_LEN] {
+    let mut u = hmac_sha256(password, &[salt, &1u32.to_be_bytes()].concat());
+    let mut res = u;
+
+    for _ in 1..rounds {
+        u = hmac_sha256(password, &u);
+
+        for (r, b) in res.iter_mut().zip(u) {
+            *r ^= b;
+        }
+    }
+
+    res
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [hi, lo] => Some((hex_digit(*hi)? << 4) | hex_digit(*lo)?),
+            _ => None,
+        })
+        .collect()
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Hashes `password` with a random salt, in the format stored in the user database.
+pub(crate) fn hash(password: &str) -> String {
+    let mut salt = [0; SALT_LEN];
+    random::fill(&mut salt);
+
+    let key = pbkdf2_sha256(password.as_bytes(), &salt, ROUNDS);
+    format!("{}${}${}${}", SCHEME, ROUNDS, to_hex(&salt), to_hex(&key))
+}
+
+/// Whether `password` matches `hash`, which was made by `hash`.
+pub(crate) fn verify(password: &str, hash: &str) -> bool {
+    let mut fields = hash.split('$');
+
+    let (Some(SCHEME), Some(rounds), Some(salt), Some(key), None) = (
+        fields.next(),
+        fields.next(),
+        fields.next(),
+        fields.next(),
+        fields.next(),
+    ) else {
+        return false;
+    };
+
+    let (Ok(rounds), Some(salt), Some(key)) = (rounds.parse(), from_hex(salt), from_hex(key))
+    else {
+        return false;
+    };
+
+    // every byte is compared so the time taken does not tell where they differ
+    let res = pbkdf2_sha256(password.as_bytes(), &salt, rounds);
+    key.len() == res.len() && key.iter().zip(res).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
@@ -35,4 +35,15 @@ pub fn init(boot_info: &'static BootInfo) {
         // if that architecture is the target architecture
         k::arch::mem::init(boot_info);
     }
+
+    k::io::ata::init();
+    k::fs::init();
+}
+
+/// Logs a user in when the root filesystem has a user database, asking until a login succeeds.
+pub fn login() {
+    // without a database the kernel stays root
+    if k::user::login().is_none() {
+        k::io::kprint!("No user database, staying root\n");
+    }
 }
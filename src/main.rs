@@ -14,6 +14,7 @@ entry_point!(kmain);
 
 fn kmain(boot_info: &'static BootInfo) -> ! {
     prestige::init(boot_info);
+    prestige::login();
 
     // print!("\x1b[?25h");
     // print!("test");